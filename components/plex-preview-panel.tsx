@@ -28,6 +28,7 @@ import { Badge } from "@/components/ui/badge"
 import { PlexSidebar } from "./plex-sidebar"
 import { resolveImageUrl } from "@/lib/utils"
 import { invoke } from "@tauri-apps/api/core"
+import { listen } from "@tauri-apps/api/event"
 import { Slider } from "@/components/ui/slider"
 
 interface ContentItem {
@@ -41,6 +42,14 @@ interface ContentItem {
   overview: string
 }
 
+interface PlayerState {
+  time_pos: number | null
+  duration: number | null
+  paused: boolean
+  eof_reached: boolean
+  idle: boolean
+}
+
 interface PlexPreviewPanelProps {
   content: ContentItem
   onClose: () => void
@@ -72,19 +81,16 @@ export function PlexPreviewPanel({ content, onClose }: PlexPreviewPanelProps) {
     }
     init()
 
-    const id = setInterval(async () => {
-      try {
-        const pos = await invoke<number>("player_position")
-        if (mounted && typeof pos === "number" && isFinite(pos)) setPosition(pos)
-        const dur = await invoke<number>("player_duration")
-        if (mounted && typeof dur === "number" && isFinite(dur)) setDuration(dur)
-      } catch (e) {
-        // noop
-      }
-    }, 500)
+    const unlisten = listen<PlayerState>("player-state", (event) => {
+      if (!mounted) return
+      const { time_pos, duration, paused } = event.payload
+      if (typeof time_pos === "number" && isFinite(time_pos)) setPosition(time_pos)
+      if (typeof duration === "number" && isFinite(duration)) setDuration(duration)
+      setPaused(paused)
+    })
     return () => {
       mounted = false
-      clearInterval(id)
+      unlisten.then((fn) => fn())
     }
   }, [selectedStreamUrl])
 
@@ -115,7 +121,7 @@ export function PlexPreviewPanel({ content, onClose }: PlexPreviewPanelProps) {
   const handleStreamSelect = (stream: any) => {
     setSelectedStreamUrl(stream.url)
     setShowStreamOptions(false)
-    invoke("player_load", { url: stream.url, start_time: 0 }).catch(console.error)
+    invoke("player_load", { url: stream.url, videoId: content.imdb_id }).catch(console.error)
   }
 
   const renderMovieContent = () => (
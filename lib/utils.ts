@@ -1,14 +1,35 @@
 import { clsx, type ClassValue } from "clsx"
 import { twMerge } from "tailwind-merge"
+import { convertFileSrc, isTauri } from "@tauri-apps/api/core"
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
 
+// Widths the backend artwork cache resizes to
+const ARTWORK_WIDTHS = [185, 342, 780]
+
+function artworkSize(tmdbSize: string): string {
+  const width = Number(tmdbSize.replace(/^w/, ""))
+  const fit = ARTWORK_WIDTHS.find((w) => w >= width)
+  return fit ? `w${fit}` : "original"
+}
+
+function isArtworkUrl(url: string): boolean {
+  return url.startsWith("artwork:") || /^https?:\/\/artwork\.localhost\//i.test(url)
+}
+
 export function resolveImageUrl(path?: string | null, tmdbSize: string = "w500"): string {
   if (!path) return ""
-  if (/^https?:\/\//i.test(path)) return path
-  // Assume TMDB path otherwise
-  const clean = path.startsWith("/") ? path : `/${path}`
-  return `https://image.tmdb.org/t/p/${tmdbSize}${clean}`
+  if (isArtworkUrl(path)) return path
+  const inTauri = isTauri()
+  let remote = path
+  if (!/^https?:\/\//i.test(path)) {
+    // Assume TMDB path otherwise; in the app the artwork cache does the resizing
+    const clean = path.startsWith("/") ? path : `/${path}`
+    remote = `https://image.tmdb.org/t/p/${inTauri ? "original" : tmdbSize}${clean}`
+  }
+  if (!inTauri) return remote
+  // Served through the disk cache; convertFileSrc gives the platform's form of the custom scheme
+  return `${convertFileSrc(artworkSize(tmdbSize), "artwork")}?url=${encodeURIComponent(remote)}`
 }
@@ -0,0 +1,589 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use crate::error::AppError;
+use crate::http_cache;
+use crate::storage;
+use crate::stream::{self, Stream, StreamInfo};
+
+pub const CINEMETA_URL: &str = "https://v3-cinemeta.strem.io";
+
+// Slow add-ons are dropped from aggregated results rather than holding up the others
+pub const ADDON_TIMEOUT: Duration = Duration::from_secs(10);
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+
+// Bounds every request, so nothing hangs on a half-dead network; artwork downloads included
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// The process-wide client; clones share one connection pool.
+pub fn http_client() -> Result<reqwest::Client, AppError> {
+    if let Some(client) = HTTP_CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Config(e.to_string()))?;
+    Ok(HTTP_CLIENT.get_or_init(|| client).clone())
+}
+
+/// A resource is declared either by name alone or with its own types and id prefixes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResourceDescriptor {
+    Short(String),
+    #[serde(rename_all = "camelCase")]
+    Full {
+        name: String,
+        #[serde(default)]
+        types: Option<Vec<String>>,
+        #[serde(default)]
+        id_prefixes: Option<Vec<String>>,
+    },
+}
+
+impl ResourceDescriptor {
+    pub fn name(&self) -> &str {
+        match self {
+            ResourceDescriptor::Short(name) => name,
+            ResourceDescriptor::Full { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtraProperty {
+    pub name: String,
+    #[serde(default)]
+    pub is_required: bool,
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub options_limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestCatalog {
+    pub r#type: String,
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub extra: Vec<ExtraProperty>,
+    // Legacy shorthand for `extra` used by older add-ons
+    #[serde(default)]
+    pub extra_supported: Vec<String>,
+    #[serde(default)]
+    pub extra_required: Vec<String>,
+}
+
+impl ManifestCatalog {
+    pub fn supports_extra(&self, name: &str) -> bool {
+        self.extra.iter().any(|e| e.name == name) || self.extra_supported.iter().any(|e| e == name)
+    }
+
+    pub fn requires_extra(&self, name: &str) -> bool {
+        self.extra.iter().any(|e| e.name == name && e.is_required) || self.extra_required.iter().any(|e| e == name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestBehaviorHints {
+    #[serde(default)]
+    pub adult: bool,
+    #[serde(default)]
+    pub p2p: bool,
+    #[serde(default)]
+    pub configurable: bool,
+    #[serde(default)]
+    pub configuration_required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub id: String,
+    pub version: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub logo: Option<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub resources: Vec<ResourceDescriptor>,
+    #[serde(default)]
+    pub types: Vec<String>,
+    #[serde(default)]
+    pub catalogs: Vec<ManifestCatalog>,
+    #[serde(default)]
+    pub addon_catalogs: Vec<ManifestCatalog>,
+    #[serde(default)]
+    pub id_prefixes: Option<Vec<String>>,
+    #[serde(default)]
+    pub behavior_hints: ManifestBehaviorHints,
+}
+
+impl Manifest {
+    /// Whether the add-on serves `resource` for this type and id, honouring per-resource
+    /// `types`/`idPrefixes` and falling back to the manifest-wide ones.
+    pub fn supports(&self, resource: &str, r#type: &str, id: &str) -> bool {
+        let Some(descriptor) = self.resources.iter().find(|r| r.name() == resource) else {
+            return false;
+        };
+        let (types, id_prefixes) = match descriptor {
+            ResourceDescriptor::Short(_) => (None, None),
+            ResourceDescriptor::Full { types, id_prefixes, .. } => (types.as_ref(), id_prefixes.as_ref()),
+        };
+        let types = types.unwrap_or(&self.types);
+        let type_ok = types.iter().any(|t| t == r#type);
+        let prefix_ok = match id_prefixes.or(self.id_prefixes.as_ref()) {
+            Some(prefixes) => prefixes.iter().any(|p| id.starts_with(p.as_str())),
+            None => true,
+        };
+        type_ok && prefix_ok
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaPreview {
+    pub id: String,
+    #[serde(default)]
+    pub r#type: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub poster: Option<String>,
+    #[serde(default)]
+    pub poster_shape: Option<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub logo: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub release_info: Option<String>,
+    #[serde(default)]
+    pub released: Option<String>,
+    #[serde(default)]
+    pub imdb_rating: Option<String>,
+    #[serde(default, rename = "imdb_id")]
+    pub imdb_id: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogResponse {
+    #[serde(default)]
+    pub metas: Vec<MetaPreview>,
+}
+
+// Cinemeta is inconsistent about `name`/`title` and `episode`/`number`, so both are kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Video {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub season: Option<u32>,
+    #[serde(default)]
+    pub episode: Option<u32>,
+    #[serde(default)]
+    pub number: Option<u32>,
+    #[serde(default)]
+    pub released: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub overview: Option<String>,
+}
+
+/// `source` is a YouTube id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trailer {
+    pub source: String,
+    #[serde(default)]
+    pub r#type: Option<String>,
+}
+
+/// Links to related pages, e.g. other titles by the same director or with the same genre.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetaLink {
+    pub name: String,
+    pub category: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaDetail {
+    #[serde(flatten)]
+    pub preview: MetaPreview,
+    #[serde(default)]
+    pub cast: Vec<String>,
+    #[serde(default)]
+    pub director: Vec<String>,
+    #[serde(default)]
+    pub writer: Vec<String>,
+    /// Free-form, e.g. `"142 min"`.
+    #[serde(default)]
+    pub runtime: Option<String>,
+    #[serde(default)]
+    pub year: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub awards: Option<String>,
+    #[serde(default)]
+    pub website: Option<String>,
+    #[serde(default)]
+    pub trailers: Vec<Trailer>,
+    #[serde(default)]
+    pub links: Vec<MetaLink>,
+    #[serde(default)]
+    pub videos: Vec<Video>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetaResponse {
+    pub meta: MetaDetail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamsResponse {
+    #[serde(default)]
+    pub streams: Vec<Stream>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subtitle {
+    // Optional in practice; several add-ons only send `url` and `lang`
+    #[serde(default)]
+    pub id: Option<String>,
+    pub url: String,
+    pub lang: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitlesResponse {
+    #[serde(default)]
+    pub subtitles: Vec<Subtitle>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddonDescriptor {
+    pub transport_url: String,
+    #[serde(default)]
+    pub transport_name: Option<String>,
+    pub manifest: Manifest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddonCatalogResponse {
+    #[serde(default)]
+    pub addons: Vec<AddonDescriptor>,
+}
+
+// Percent-encode everything outside the URL unreserved set
+fn encode_component(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Client for any add-on speaking the Stremio protocol (`<base>/<resource>/<type>/<id>[/<extra>].json`).
+#[derive(Clone)]
+pub struct AddonClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl AddonClient {
+    /// Accepts either the add-on base URL or its `manifest.json` URL.
+    pub fn new(http: reqwest::Client, url: &str) -> Self {
+        let base = url.trim().trim_end_matches('/');
+        let base = base.strip_suffix("/manifest.json").unwrap_or(base);
+        Self { http, base_url: base.to_string() }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        let body = http_cache::get(&self.http, url).await?;
+        serde_json::from_str::<T>(&body).map_err(|e| {
+            eprintln!("Error parsing response from {}: {}", url, e);
+            AppError::from(e)
+        })
+    }
+
+    pub async fn manifest(&self) -> Result<Manifest, AppError> {
+        self.get(&format!("{}/manifest.json", self.base_url)).await
+    }
+
+    /// Request any resource; `extra` becomes the `genre=Drama&skip=100` path segment.
+    pub async fn resource<T: DeserializeOwned>(
+        &self,
+        resource: &str,
+        r#type: &str,
+        id: &str,
+        extra: &[(&str, &str)],
+    ) -> Result<T, AppError> {
+        let mut url = format!("{}/{}/{}/{}", self.base_url, resource, r#type, id);
+        if !extra.is_empty() {
+            let extra = extra
+                .iter()
+                .map(|(k, v)| format!("{}={}", encode_component(k), encode_component(v)))
+                .collect::<Vec<_>>()
+                .join("&");
+            url.push('/');
+            url.push_str(&extra);
+        }
+        url.push_str(".json");
+        self.get(&url).await
+    }
+
+    pub async fn catalog(&self, r#type: &str, id: &str, extra: &[(&str, &str)]) -> Result<CatalogResponse, AppError> {
+        self.resource("catalog", r#type, id, extra).await
+    }
+
+    pub async fn meta(&self, r#type: &str, id: &str) -> Result<MetaResponse, AppError> {
+        self.resource("meta", r#type, id, &[]).await
+    }
+
+    pub async fn streams(&self, r#type: &str, id: &str) -> Result<StreamsResponse, AppError> {
+        self.resource("stream", r#type, id, &[]).await
+    }
+
+    pub async fn subtitles(&self, r#type: &str, id: &str, extra: &[(&str, &str)]) -> Result<SubtitlesResponse, AppError> {
+        self.resource("subtitles", r#type, id, extra).await
+    }
+
+    pub async fn addon_catalog(&self, r#type: &str, id: &str) -> Result<AddonCatalogResponse, AppError> {
+        self.resource("addon_catalog", r#type, id, &[]).await
+    }
+}
+
+/// An add-on the user installed, with the manifest fetched at install time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledAddon {
+    pub transport_url: String,
+    pub manifest: Manifest,
+    pub position: u32,
+    pub enabled: bool,
+}
+
+impl InstalledAddon {
+    pub fn source(&self) -> AddonSource {
+        AddonSource {
+            addon_id: self.manifest.id.clone(),
+            addon_name: self.manifest.name.clone(),
+            transport_url: self.transport_url.clone(),
+        }
+    }
+}
+
+/// Identifies which add-on an aggregated result came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct AddonSource {
+    pub addon_id: String,
+    pub addon_name: String,
+    pub transport_url: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedStream {
+    pub source: AddonSource,
+    #[serde(flatten)]
+    pub stream: Stream,
+    pub info: StreamInfo,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AddonFailure {
+    pub source: AddonSource,
+    pub error: AppError,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AggregatedStreams {
+    pub streams: Vec<SourcedStream>,
+    pub failures: Vec<AddonFailure>,
+}
+
+/// Run `request` against every enabled add-on serving `resource` for this type and id
+/// concurrently, giving each [`ADDON_TIMEOUT`]. Results keep the add-on install order.
+async fn fan_out<T, F, Fut>(
+    http: &reqwest::Client,
+    addons: &[InstalledAddon],
+    resource: &str,
+    r#type: &str,
+    id: &str,
+    request: F,
+) -> Vec<(AddonSource, Result<T, AppError>)>
+where
+    T: Send + 'static,
+    F: Fn(AddonClient) -> Fut,
+    Fut: Future<Output = Result<T, AppError>> + Send + 'static,
+{
+    let mut requests = JoinSet::new();
+    let eligible = addons
+        .iter()
+        .filter(|a| a.enabled && a.manifest.supports(resource, r#type, id));
+    for (index, addon) in eligible.enumerate() {
+        let pending = request(AddonClient::new(http.clone(), &addon.transport_url));
+        let source = addon.source();
+        requests.spawn(async move {
+            let result = match timeout(ADDON_TIMEOUT, pending).await {
+                Ok(result) => result,
+                Err(_) => Err(AppError::Network(format!("timed out after {}s", ADDON_TIMEOUT.as_secs()))),
+            };
+            (index, source, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = requests.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => eprintln!("{} request task failed: {}", resource, e),
+        }
+    }
+    results.sort_by_key(|(index, _, _)| *index);
+    results.into_iter().map(|(_, source, result)| (source, result)).collect()
+}
+
+/// Query every enabled add-on that serves streams for this type and id concurrently.
+/// Add-ons that fail or time out are reported in `failures` instead of failing the
+/// whole request.
+pub async fn aggregate_streams(http: &reqwest::Client, addons: &[InstalledAddon], r#type: &str, id: &str) -> AggregatedStreams {
+    let results = fan_out(http, addons, "stream", r#type, id, |client| {
+        let (r#type, id) = (r#type.to_string(), id.to_string());
+        async move { client.streams(&r#type, &id).await }
+    })
+    .await;
+
+    let mut aggregated = AggregatedStreams::default();
+    for (source, result) in results {
+        match result {
+            Ok(response) => aggregated.streams.extend(
+                response
+                    .streams
+                    .into_iter()
+                    .map(|stream| SourcedStream {
+                        source: source.clone(),
+                        info: stream::parse_info(&stream),
+                        stream,
+                    }),
+            ),
+            Err(error) => aggregated.failures.push(AddonFailure { source, error }),
+        }
+    }
+    aggregated
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedSubtitle {
+    pub source: AddonSource,
+    #[serde(flatten)]
+    pub subtitle: Subtitle,
+}
+
+/// Subtitles from every add-on, grouped by their `lang` code.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AggregatedSubtitles {
+    pub languages: BTreeMap<String, Vec<SourcedSubtitle>>,
+    pub failures: Vec<AddonFailure>,
+}
+
+/// Query every enabled add-on that serves subtitles for this type and id. `extra` carries
+/// hints such as `videoHash`, `videoSize` or `filename` that help add-ons match the file.
+pub async fn aggregate_subtitles(
+    http: &reqwest::Client,
+    addons: &[InstalledAddon],
+    r#type: &str,
+    id: &str,
+    extra: &BTreeMap<String, String>,
+) -> AggregatedSubtitles {
+    let results = fan_out(http, addons, "subtitles", r#type, id, |client| {
+        let (r#type, id, extra) = (r#type.to_string(), id.to_string(), extra.clone());
+        async move {
+            let extra: Vec<(&str, &str)> = extra.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+            client.subtitles(&r#type, &id, &extra).await
+        }
+    })
+    .await;
+
+    let mut aggregated = AggregatedSubtitles::default();
+    for (source, result) in results {
+        match result {
+            Ok(response) => {
+                for subtitle in response.subtitles {
+                    let lang = subtitle.lang.to_lowercase();
+                    aggregated
+                        .languages
+                        .entry(lang)
+                        .or_default()
+                        .push(SourcedSubtitle { source: source.clone(), subtitle });
+                }
+            }
+            Err(error) => aggregated.failures.push(AddonFailure { source, error }),
+        }
+    }
+    aggregated
+}
+
+/// Download a remote subtitle to a temporary file, so mpv's `sub-add` never waits on the
+/// network inside the player thread.
+pub async fn download_subtitle(http: &reqwest::Client, url: &str) -> Result<PathBuf, AppError> {
+    let body = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())?
+        .bytes()
+        .await?;
+    // Add-on download URLs often have no extension; mpv still probes the contents
+    let extension = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            let name = u.path_segments()?.next_back()?.to_string();
+            let (_, ext) = name.rsplit_once('.')?;
+            (ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric())).then(|| ext.to_lowercase())
+        })
+        .unwrap_or_else(|| "srt".to_string());
+    let dir = std::env::temp_dir().join("unstrem-subtitles");
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!("{}.{extension}", storage::cache_key(url)));
+    tokio::fs::write(&path, &body).await?;
+    Ok(path)
+}
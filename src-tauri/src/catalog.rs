@@ -0,0 +1,274 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use crate::addon::{
+    self, AddonClient, AddonFailure, AddonSource, ExtraProperty, InstalledAddon, ManifestCatalog, MetaPreview,
+};
+use crate::error::AppError;
+use crate::storage::Storage;
+
+/// One catalog an add-on offers, i.e. one row the home screen can render.
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogRow {
+    pub source: AddonSource,
+    pub r#type: String,
+    pub id: String,
+    pub name: Option<String>,
+    pub extra: Vec<ExtraProperty>,
+}
+
+impl CatalogRow {
+    fn new(source: AddonSource, catalog: &ManifestCatalog) -> Self {
+        // Fold the legacy `extraSupported`/`extraRequired` lists into `extra`
+        let mut extra = catalog.extra.clone();
+        for name in &catalog.extra_supported {
+            if !extra.iter().any(|e| &e.name == name) {
+                extra.push(ExtraProperty {
+                    name: name.clone(),
+                    is_required: catalog.extra_required.contains(name),
+                    options: Vec::new(),
+                    options_limit: None,
+                });
+            }
+        }
+        Self {
+            source,
+            r#type: catalog.r#type.clone(),
+            id: catalog.id.clone(),
+            name: catalog.name.clone(),
+            extra,
+        }
+    }
+}
+
+/// Enabled add-ons that have catalogs, in install order. Until the user installs one,
+/// Cinemeta is used so the home screen is never empty.
+pub async fn catalog_addons(http: &reqwest::Client, storage: &Storage) -> Result<Vec<InstalledAddon>, AppError> {
+    let addons: Vec<InstalledAddon> = storage
+        .installed_addons()?
+        .into_iter()
+        .filter(|a| a.enabled && !a.manifest.catalogs.is_empty())
+        .collect();
+    if !addons.is_empty() {
+        return Ok(addons);
+    }
+    let manifest = AddonClient::new(http.clone(), addon::CINEMETA_URL).manifest().await?;
+    Ok(vec![InstalledAddon {
+        transport_url: addon::CINEMETA_URL.to_string(),
+        manifest,
+        position: 0,
+        enabled: true,
+    }])
+}
+
+pub fn list_catalogs(addons: &[InstalledAddon]) -> Vec<CatalogRow> {
+    addons
+        .iter()
+        .flat_map(|a| a.manifest.catalogs.iter().map(move |c| CatalogRow::new(a.source(), c)))
+        .collect()
+}
+
+const SKIP: &str = "skip";
+const SEARCH: &str = "search";
+
+/// A page of catalog items. `next` is an opaque cursor for the following page and is
+/// `None` once the end of the catalog is reached.
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogPage {
+    pub metas: Vec<MetaPreview>,
+    pub next: Option<String>,
+}
+
+fn parse_cursor(cursor: Option<&str>) -> Result<u64, AppError> {
+    cursor.map_or(Ok(0), |c| {
+        c.parse()
+            .map_err(|_| AppError::Config(format!("invalid catalog cursor {c}")))
+    })
+}
+
+/// Where the page after one starting at `skip` begins. Add-ons may pin the steps through
+/// the `skip` options; otherwise the next page starts after the items just returned.
+fn next_skip(row: &CatalogRow, skip: u64, returned: usize) -> Option<u64> {
+    let property = row.extra.iter().find(|e| e.name == SKIP)?;
+    if returned == 0 {
+        return None;
+    }
+    if property.options.is_empty() {
+        return Some(skip + returned as u64);
+    }
+    property
+        .options
+        .iter()
+        .filter_map(|o| o.parse::<u64>().ok())
+        .filter(|o| *o > skip)
+        .min()
+}
+
+/// Fetch one page of a catalog, checking the extra args against what the manifest declares.
+/// Paging is driven by `cursor`; a `skip` passed in `extra` is ignored.
+pub async fn fetch_catalog(
+    http: &reqwest::Client,
+    addons: &[InstalledAddon],
+    transport_url: &str,
+    r#type: &str,
+    id: &str,
+    extra: &BTreeMap<String, String>,
+    cursor: Option<&str>,
+) -> Result<CatalogPage, AppError> {
+    let skip = parse_cursor(cursor)?;
+    let mut extra = extra.clone();
+    extra.remove(SKIP);
+    let addon = addons
+        .iter()
+        .find(|a| a.transport_url == transport_url)
+        .ok_or_else(|| AppError::NotFound(format!("add-on {transport_url} is not installed")))?;
+    let catalog = addon
+        .manifest
+        .catalogs
+        .iter()
+        .find(|c| c.r#type == r#type && c.id == id)
+        .ok_or_else(|| AppError::NotFound(format!("{} has no {type} catalog {id}", addon.manifest.name)))?;
+    let row = CatalogRow::new(addon.source(), catalog);
+    if let Some(missing) = row
+        .extra
+        .iter()
+        .find(|e| catalog.requires_extra(&e.name) && !extra.contains_key(&e.name))
+    {
+        return Err(AppError::Config(format!("catalog {id} requires {}", missing.name)));
+    }
+    if let Some(name) = extra.keys().find(|name| !catalog.supports_extra(name)) {
+        return Err(AppError::Config(format!("catalog {id} does not support {name}")));
+    }
+
+    if skip > 0 && !catalog.supports_extra(SKIP) {
+        return Err(AppError::Config(format!("catalog {id} is not paginated")));
+    }
+    // Stremio leaves `skip` out for the first page, and some add-ons rely on that
+    if skip > 0 {
+        extra.insert(SKIP.to_string(), skip.to_string());
+    }
+    let args: Vec<(&str, &str)> = extra.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let client = AddonClient::new(http.clone(), &addon.transport_url);
+    let metas = client.catalog(r#type, id, &args).await?.metas;
+    let next = next_skip(&row, skip, metas.len()).map(|s| s.to_string());
+    Ok(CatalogPage { metas, next })
+}
+
+// Catalogs that need more than the query (e.g. a mandatory genre) cannot be searched
+fn is_searchable(catalog: &ManifestCatalog) -> bool {
+    let mut required = catalog
+        .extra
+        .iter()
+        .filter(|e| e.is_required)
+        .map(|e| &e.name)
+        .chain(&catalog.extra_required);
+    catalog.supports_extra(SEARCH) && required.all(|name| name == SEARCH)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchResults {
+    /// Results keyed by type (`movie`, `series`, ...), in add-on install order.
+    pub groups: BTreeMap<String, Vec<MetaPreview>>,
+    pub failures: Vec<AddonFailure>,
+}
+
+/// Query every catalog that declares the `search` extra concurrently and merge the results.
+/// Items found by several add-ons are kept once, from the first add-on in install order.
+pub async fn search(http: &reqwest::Client, addons: &[InstalledAddon], query: &str) -> SearchResults {
+    let mut requests = JoinSet::new();
+    let searchable = addons
+        .iter()
+        .flat_map(|a| a.manifest.catalogs.iter().map(move |c| (a, c)))
+        .filter(|(_, c)| is_searchable(c));
+    for (index, (addon, catalog)) in searchable.enumerate() {
+        let client = AddonClient::new(http.clone(), &addon.transport_url);
+        let source = addon.source();
+        let (r#type, id, query) = (catalog.r#type.clone(), catalog.id.clone(), query.to_string());
+        requests.spawn(async move {
+            let result = match timeout(addon::ADDON_TIMEOUT, client.catalog(&r#type, &id, &[(SEARCH, &query)])).await {
+                Ok(result) => result,
+                Err(_) => Err(AppError::Network(format!(
+                    "timed out after {}s",
+                    addon::ADDON_TIMEOUT.as_secs()
+                ))),
+            };
+            (index, source, r#type, result)
+        });
+    }
+
+    let mut responses = Vec::new();
+    while let Some(joined) = requests.join_next().await {
+        match joined {
+            Ok(response) => responses.push(response),
+            Err(e) => eprintln!("Search request task failed: {}", e),
+        }
+    }
+    responses.sort_by_key(|(index, ..)| *index);
+
+    let mut results = SearchResults::default();
+    let mut seen = HashSet::new();
+    for (_, source, r#type, result) in responses {
+        match result {
+            Ok(response) => {
+                let group = results.groups.entry(r#type).or_default();
+                for meta in response.metas {
+                    let key = meta.imdb_id.clone().unwrap_or_else(|| meta.id.clone());
+                    if seen.insert(key) {
+                        group.push(meta);
+                    }
+                }
+            }
+            Err(error) => results.failures.push(AddonFailure { source, error }),
+        }
+    }
+    results.groups.retain(|_, metas| !metas.is_empty());
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(catalog: &str) -> CatalogRow {
+        let catalog: ManifestCatalog = serde_json::from_str(catalog).unwrap();
+        let source = AddonSource {
+            addon_id: "com.linvo.cinemeta".to_string(),
+            addon_name: "Cinemeta".to_string(),
+            transport_url: addon::CINEMETA_URL.to_string(),
+        };
+        CatalogRow::new(source, &catalog)
+    }
+
+    #[test]
+    fn skips_past_returned_items() {
+        let row = row(
+            r#"{"type":"movie","id":"top","name":"Popular","extra":[{"name":"genre","options":["Action","Drama"]},{"name":"search"},{"name":"skip"}],"extraSupported":["search","genre","skip"]}"#,
+        );
+        assert_eq!(next_skip(&row, 0, 50), Some(50));
+        assert_eq!(next_skip(&row, 50, 50), Some(100));
+        assert_eq!(next_skip(&row, 100, 0), None);
+    }
+
+    #[test]
+    fn follows_pinned_skip_options() {
+        let row = row(r#"{"type":"series","id":"anime","extra":[{"name":"skip","options":["0","100","200"]}]}"#);
+        // A short page still moves to the next pinned step
+        assert_eq!(next_skip(&row, 0, 37), Some(100));
+        assert_eq!(next_skip(&row, 100, 100), Some(200));
+        assert_eq!(next_skip(&row, 200, 100), None);
+    }
+
+    #[test]
+    fn legacy_extra_supported() {
+        let row = row(r#"{"type":"movie","id":"last-videos","extraSupported":["skip"]}"#);
+        assert_eq!(next_skip(&row, 0, 100), Some(100));
+    }
+
+    #[test]
+    fn no_paging_without_skip() {
+        let row = row(r#"{"type":"movie","id":"year","extra":[{"name":"genre","isRequired":true,"options":["2024"]}]}"#);
+        assert_eq!(next_skip(&row, 0, 100), None);
+    }
+}
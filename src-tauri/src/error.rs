@@ -0,0 +1,131 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+/// Error returned by every command; the frontend branches on `kind`. Every kind serializes
+/// with a string `message`, and `Http` adds its `status`:
+/// `{"kind":"Http","status":404,"message":"..."}`.
+#[derive(Debug, Clone)]
+pub enum AppError {
+    /// The host could not be reached, including while offline.
+    Network(String),
+    /// The server answered with an error status.
+    Http { status: u16, message: String },
+    /// A response or stored value could not be decoded.
+    Parse(String),
+    /// Missing or invalid configuration, e.g. no stream provider or a bad add-on URL.
+    Config(String),
+    Player(String),
+    Storage(String),
+    NotFound(String),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Network(msg) => write!(f, "network error: {msg}"),
+            AppError::Http { status, message } => write!(f, "HTTP {status}: {message}"),
+            AppError::Parse(msg) => write!(f, "invalid data: {msg}"),
+            AppError::Config(msg) => write!(f, "{msg}"),
+            AppError::Player(msg) => write!(f, "player error: {msg}"),
+            AppError::Storage(msg) => write!(f, "storage error: {msg}"),
+            AppError::NotFound(msg) => write!(f, "not found: {msg}"),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (kind, status, message) = match self {
+            AppError::Network(msg) => ("Network", None, msg),
+            AppError::Http { status, message } => ("Http", Some(status), message),
+            AppError::Parse(msg) => ("Parse", None, msg),
+            AppError::Config(msg) => ("Config", None, msg),
+            AppError::Player(msg) => ("Player", None, msg),
+            AppError::Storage(msg) => ("Storage", None, msg),
+            AppError::NotFound(msg) => ("NotFound", None, msg),
+        };
+        let mut state = serializer.serialize_struct("AppError", if status.is_some() { 3 } else { 2 })?;
+        state.serialize_field("kind", kind)?;
+        if let Some(status) = status {
+            state.serialize_field("status", status)?;
+        }
+        state.serialize_field("message", message)?;
+        state.end()
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => AppError::Http {
+                status: status.as_u16(),
+                message: e.to_string(),
+            },
+            None if e.is_decode() => AppError::Parse(e.to_string()),
+            None => AppError::Network(e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Parse(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Storage(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Storage(e.to_string())
+    }
+}
+
+impl From<libmpv2::Error> for AppError {
+    fn from(e: libmpv2::Error) -> Self {
+        AppError::Player(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn serialized(error: AppError) -> serde_json::Value {
+        serde_json::to_value(error).unwrap()
+    }
+
+    #[test]
+    fn http_has_status_next_to_message() {
+        let error = AppError::Http { status: 404, message: "HTTP status client error (404 Not Found)".to_string() };
+        assert_eq!(
+            serialized(error),
+            json!({"kind": "Http", "status": 404, "message": "HTTP status client error (404 Not Found)"})
+        );
+    }
+
+    #[test]
+    fn string_kinds_have_message() {
+        let cases = [
+            (AppError::Network("could not reach https://v3-cinemeta.strem.io".to_string()), "Network"),
+            (AppError::Parse("expected value at line 1 column 1".to_string()), "Parse"),
+            (AppError::Config("no stream provider configured".to_string()), "Config"),
+            (AppError::Player("player stopped while handling the command".to_string()), "Player"),
+            (AppError::Storage("database is locked".to_string()), "Storage"),
+            (AppError::NotFound("add-on https://example.org is not installed".to_string()), "NotFound"),
+        ];
+        for (error, kind) in cases {
+            let value = serialized(error);
+            assert_eq!(value.as_object().map(|o| o.len()), Some(2), "{value}");
+            assert_eq!(value["kind"], kind);
+            assert!(value["message"].is_string(), "{value}");
+        }
+    }
+}
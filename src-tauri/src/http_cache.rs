@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::AppError;
+use crate::network;
+use crate::storage::{self, unix_now};
+
+const CACHE_DIR: &str = "http-cache";
+// How long a response without `staleError` may still be served when the add-on is unreachable
+const DEFAULT_STALE_ERROR: u64 = 7 * 24 * 60 * 60;
+
+/// Caching hints Stremio add-ons put at the top level of resource responses, in seconds.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheHints {
+    #[serde(default)]
+    cache_max_age: Option<u64>,
+    #[serde(default)]
+    stale_revalidate: Option<u64>,
+    #[serde(default)]
+    stale_error: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    fetched_at: u64,
+    max_age: u64,
+    stale_revalidate: u64,
+    stale_error: u64,
+    body: String,
+}
+
+impl CacheEntry {
+    fn new(url: &str, body: String) -> Self {
+        let hints: CacheHints = serde_json::from_str(&body).unwrap_or_default();
+        Self {
+            url: url.to_string(),
+            fetched_at: unix_now(),
+            max_age: hints.cache_max_age.unwrap_or(0),
+            stale_revalidate: hints.stale_revalidate.unwrap_or(0),
+            stale_error: hints.stale_error.unwrap_or(DEFAULT_STALE_ERROR),
+            body,
+        }
+    }
+
+    fn age(&self) -> u64 {
+        unix_now().saturating_sub(self.fetched_at)
+    }
+}
+
+fn entry_path(url: &str) -> Result<PathBuf, AppError> {
+    Ok(storage::cache_dir(CACHE_DIR)?.join(format!("{}.json", storage::cache_key(url))))
+}
+
+fn read_entry(url: &str) -> Option<CacheEntry> {
+    let data = fs::read(entry_path(url).ok()?).ok()?;
+    // A hash collision shows up as an entry for another URL
+    serde_json::from_slice::<CacheEntry>(&data).ok().filter(|e| e.url == url)
+}
+
+fn write_entry(entry: &CacheEntry) -> Result<(), AppError> {
+    let path = entry_path(&entry.url)?;
+    let tmp = path.with_extension("json.tmp");
+    let data = serde_json::to_vec(entry)?;
+    fs::write(&tmp, data)?;
+    Ok(fs::rename(&tmp, &path)?)
+}
+
+// URLs whose refresh failed because their host was unreachable, refetched by the network monitor
+fn pending() -> &'static Mutex<HashSet<String>> {
+    static PENDING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    PENDING.get_or_init(Default::default)
+}
+
+fn queue_retry(url: &str) {
+    pending().lock().unwrap_or_else(|e| e.into_inner()).insert(url.to_string());
+}
+
+/// Refetch everything that failed to reach its host so the cache is current again.
+pub async fn retry_pending(http: &reqwest::Client) {
+    let urls: Vec<String> = pending().lock().unwrap_or_else(|e| e.into_inner()).drain().collect();
+    for url in urls {
+        let _ = fetch_remote(http, &url).await;
+    }
+}
+
+async fn fetch_remote(http: &reqwest::Client, url: &str) -> Result<String, AppError> {
+    let response = http.get(url).send().await.and_then(|r| r.error_for_status());
+    let body = match response {
+        Ok(response) => response.text().await,
+        Err(e) => Err(e),
+    };
+    let body = match body {
+        Ok(body) => {
+            network::set_offline(false);
+            body
+        }
+        Err(e) if network::is_offline_error(&e) => {
+            eprintln!("Error fetching {}: {}", url, e);
+            network::report_unreachable();
+            queue_retry(url);
+            return Err(AppError::Network(format!("could not reach {url}")));
+        }
+        Err(e) => {
+            eprintln!("Error fetching {}: {}", url, e);
+            return Err(e.into());
+        }
+    };
+    if let Err(e) = write_entry(&CacheEntry::new(url, body.clone())) {
+        eprintln!("http cache write error: {e}");
+    }
+    Ok(body)
+}
+
+/// GET `url`, going through the disk cache. Fresh entries are served directly, entries within
+/// `staleRevalidate` are served while being refreshed in the background, and anything within
+/// `staleError` is served when the request fails. While offline any cached entry is served.
+pub async fn get(http: &reqwest::Client, url: &str) -> Result<String, AppError> {
+    let Some(entry) = read_entry(url) else {
+        return fetch_remote(http, url).await;
+    };
+    let age = entry.age();
+    if age < entry.max_age {
+        return Ok(entry.body);
+    }
+    if network::is_offline() {
+        queue_retry(url);
+        return Ok(entry.body);
+    }
+    if age < entry.max_age + entry.stale_revalidate {
+        let (http, url) = (http.clone(), url.to_string());
+        tokio::spawn(async move {
+            let _ = fetch_remote(&http, &url).await;
+        });
+        return Ok(entry.body);
+    }
+    match fetch_remote(http, url).await {
+        Ok(body) => Ok(body),
+        Err(e) if network::is_offline() || age < entry.max_age + entry.stale_error => {
+            eprintln!("Serving stale {} ({}s old): {}", url, age, e);
+            Ok(entry.body)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Total size of the cache on disk, in bytes.
+pub fn size() -> Result<u64, AppError> {
+    storage::dir_size(&storage::cache_dir(CACHE_DIR)?)
+}
+
+pub fn clear() -> Result<(), AppError> {
+    storage::clear_dir(&storage::cache_dir(CACHE_DIR)?)
+}
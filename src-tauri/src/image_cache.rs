@@ -0,0 +1,238 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::ImageFormat;
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tauri::http::{header, Request, Response, StatusCode};
+
+use crate::addon;
+use crate::error::AppError;
+use crate::settings;
+use crate::storage::{self, Storage};
+
+/// Artwork is requested as `artwork://localhost/<size>?url=<remote url>`
+/// (`http://artwork.localhost/...` on Windows), where size is `w185`, `w342`, `w780` or `original`.
+pub const SCHEME: &str = "artwork";
+pub const MAX_BYTES_KEY: &str = "image_cache_max_bytes";
+
+const CACHE_DIR: &str = "images";
+const DEFAULT_MAX_BYTES: u64 = 512 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 85;
+// Eviction trims the cache to this share of the cap, so the next misses don't each trigger a scan
+const EVICT_TARGET_PERCENT: u64 = 90;
+
+#[derive(Debug, Clone, Copy)]
+enum ImageSize {
+    Small,
+    Medium,
+    Large,
+    Original,
+}
+
+impl ImageSize {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "w185" => Some(ImageSize::Small),
+            "w342" => Some(ImageSize::Medium),
+            "w780" => Some(ImageSize::Large),
+            "original" => Some(ImageSize::Original),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ImageSize::Small => "w185",
+            ImageSize::Medium => "w342",
+            ImageSize::Large => "w780",
+            ImageSize::Original => "original",
+        }
+    }
+
+    fn width(self) -> Option<u32> {
+        match self {
+            ImageSize::Small => Some(185),
+            ImageSize::Medium => Some(342),
+            ImageSize::Large => Some(780),
+            ImageSize::Original => None,
+        }
+    }
+}
+
+/// The `artwork://` URL serving `remote` at `size`, for the webview to load.
+pub fn artwork_url(remote: &str, size: &str) -> String {
+    let base = if cfg!(windows) {
+        format!("http://{SCHEME}.localhost/{size}")
+    } else {
+        format!("{SCHEME}://localhost/{size}")
+    };
+    match reqwest::Url::parse(&base) {
+        Ok(mut url) => {
+            url.query_pairs_mut().append_pair("url", remote);
+            url.into()
+        }
+        Err(_) => remote.to_string(),
+    }
+}
+
+fn entry_path(url: &str, size: ImageSize) -> Result<PathBuf, AppError> {
+    Ok(storage::cache_dir(CACHE_DIR)?.join(format!("{}-{}", storage::cache_key(url), size.name())))
+}
+
+fn parse_request(uri: &str) -> Result<(ImageSize, String), AppError> {
+    let parsed = reqwest::Url::parse(uri).map_err(|e| AppError::Config(e.to_string()))?;
+    let size = parsed.path().trim_matches('/');
+    let size = ImageSize::parse(size).ok_or_else(|| AppError::NotFound(format!("unknown image size {size}")))?;
+    let remote = parsed
+        .query_pairs()
+        .find(|(k, _)| k == "url")
+        .map(|(_, v)| v.into_owned())
+        .ok_or_else(|| AppError::Config("missing url parameter".to_string()))?;
+    if !remote.starts_with("http://") && !remote.starts_with("https://") {
+        return Err(AppError::Config(format!("unsupported image url {remote}")));
+    }
+    Ok((size, remote))
+}
+
+/// Downscale to the requested width; images already small enough are kept as downloaded.
+fn resize(bytes: Vec<u8>, size: ImageSize) -> Result<Vec<u8>, AppError> {
+    let Some(width) = size.width() else {
+        return Ok(bytes);
+    };
+    let img = image::load_from_memory(&bytes).map_err(|e| AppError::Parse(e.to_string()))?;
+    if img.width() <= width {
+        return Ok(bytes);
+    }
+    let img = img.resize(width, u32::MAX, FilterType::Lanczos3);
+    let mut out = Vec::new();
+    // Logos need their transparency; everything else is smaller as JPEG
+    if img.color().has_alpha() {
+        img.write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+            .map_err(|e| AppError::Parse(e.to_string()))?;
+    } else {
+        img.to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))
+            .map_err(|e| AppError::Parse(e.to_string()))?;
+    }
+    Ok(out)
+}
+
+// Access time for LRU purposes is tracked through the file's mtime
+fn touch(path: &Path) {
+    if let Ok(file) = File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+// Running size of the cache, so a miss only scans the directory once the cap is passed
+fn cached_bytes() -> &'static AtomicU64 {
+    static CACHED_BYTES: OnceLock<AtomicU64> = OnceLock::new();
+    CACHED_BYTES.get_or_init(|| AtomicU64::new(size().unwrap_or(0)))
+}
+
+fn max_bytes(storage: &Storage) -> u64 {
+    settings::load(storage, MAX_BYTES_KEY)
+        .ok()
+        .flatten()
+        .unwrap_or(DEFAULT_MAX_BYTES)
+}
+
+/// Remove least recently used images until the cache fits comfortably in `max_bytes`.
+pub fn evict(max_bytes: u64) -> Result<(), AppError> {
+    let target = max_bytes / 100 * EVICT_TARGET_PERCENT;
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = fs::read_dir(storage::cache_dir(CACHE_DIR)?)?
+        .flatten()
+        .filter_map(|e| {
+            let meta = e.metadata().ok().filter(|m| m.is_file())?;
+            Some((e.path(), meta.len(), meta.modified().ok()?))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+    for (path, len, _) in files {
+        if total <= target {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+    cached_bytes().store(total, Ordering::Relaxed);
+    Ok(())
+}
+
+async fn load(storage: &Storage, uri: &str) -> Result<Vec<u8>, AppError> {
+    let (size, remote) = parse_request(uri)?;
+    let path = entry_path(&remote, size)?;
+    if let Ok(data) = fs::read(&path) {
+        touch(&path);
+        return Ok(data);
+    }
+
+    let http = addon::http_client()?;
+    let bytes = http
+        .get(&remote)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())?
+        .bytes()
+        .await?
+        .to_vec();
+    let data = tokio::task::spawn_blocking(move || resize(bytes, size))
+        .await
+        .map_err(|e| AppError::Parse(e.to_string()))??;
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, &data)?;
+    fs::rename(&tmp, &path)?;
+    let added = data.len() as u64;
+    let total = cached_bytes().fetch_add(added, Ordering::Relaxed) + added;
+    let max = max_bytes(storage);
+    if total > max {
+        if let Err(e) = evict(max) {
+            eprintln!("image cache eviction error: {e}");
+        }
+    }
+    Ok(data)
+}
+
+/// Handler for the `artwork` URI scheme.
+pub async fn serve(storage: Storage, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let uri = request.uri().to_string();
+    let (status, content_type, body) = match load(&storage, &uri).await {
+        Ok(data) => {
+            let mime = image::guess_format(&data).map_or("application/octet-stream", |f| f.to_mime_type());
+            (StatusCode::OK, mime, data)
+        }
+        Err(e) => {
+            eprintln!("Error serving {}: {}", uri, e);
+            (StatusCode::NOT_FOUND, "text/plain", e.to_string().into_bytes())
+        }
+    };
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "max-age=86400")
+        .body(body)
+        .unwrap_or_default()
+}
+
+/// Total size of the image cache on disk, in bytes.
+pub fn size() -> Result<u64, AppError> {
+    storage::dir_size(&storage::cache_dir(CACHE_DIR)?)
+}
+
+pub fn clear() -> Result<(), AppError> {
+    storage::clear_dir(&storage::cache_dir(CACHE_DIR)?)?;
+    cached_bytes().store(0, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn set_max_bytes(storage: &Storage, max_bytes: u64) -> Result<(), AppError> {
+    settings::save(storage, MAX_BYTES_KEY, &max_bytes)?;
+    evict(max_bytes)
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+
+use crate::player::{Track, TrackType};
+use crate::storage::SeriesLanguages;
+
+pub const PREFERENCES_KEY: &str = "language_preferences";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleMode {
+    /// Show full subtitles in the first preferred language that has them.
+    #[default]
+    Preferred,
+    /// Only show forced subtitles (signs, foreign dialogue).
+    ForcedOnly,
+    Off,
+}
+
+/// Languages are ISO 639 codes in order of preference, e.g. `["ja", "en"]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguagePreferences {
+    pub audio: Vec<String>,
+    pub subtitles: Vec<String>,
+    pub subtitle_mode: SubtitleMode,
+}
+
+impl LanguagePreferences {
+    /// Put what was picked for a series ahead of the global order.
+    pub fn with_override(mut self, series: &SeriesLanguages) -> Self {
+        if let Some(audio) = &series.audio {
+            self.audio.retain(|l| !same_language(l, audio));
+            self.audio.insert(0, audio.clone());
+        }
+        if series.subtitles_off {
+            self.subtitle_mode = SubtitleMode::Off;
+        } else if let Some(subtitle) = &series.subtitle {
+            self.subtitles.retain(|l| !same_language(l, subtitle));
+            self.subtitles.insert(0, subtitle.clone());
+            self.subtitle_mode = SubtitleMode::Preferred;
+        }
+        self
+    }
+}
+
+// Two-letter codes and the bibliographic/terminology three-letter codes mpv may report
+const ISO_639: &[(&str, &[&str])] = &[
+    ("en", &["eng"]),
+    ("es", &["spa"]),
+    ("fr", &["fre", "fra"]),
+    ("de", &["ger", "deu"]),
+    ("it", &["ita"]),
+    ("pt", &["por"]),
+    ("nl", &["dut", "nld"]),
+    ("ru", &["rus"]),
+    ("pl", &["pol"]),
+    ("sv", &["swe"]),
+    ("no", &["nor", "nob", "nb"]),
+    ("da", &["dan"]),
+    ("fi", &["fin"]),
+    ("cs", &["cze", "ces"]),
+    ("hu", &["hun"]),
+    ("ro", &["rum", "ron"]),
+    ("el", &["gre", "ell"]),
+    ("tr", &["tur"]),
+    ("ar", &["ara"]),
+    ("he", &["heb"]),
+    ("hi", &["hin"]),
+    ("ja", &["jpn"]),
+    ("ko", &["kor"]),
+    ("zh", &["chi", "zho"]),
+    ("th", &["tha"]),
+    ("vi", &["vie"]),
+    ("id", &["ind"]),
+    ("uk", &["ukr"]),
+];
+
+/// Fold a language tag to its two-letter code where known (`eng`, `en-US` → `en`).
+fn normalize(code: &str) -> String {
+    let code = code.trim().to_lowercase();
+    let primary = code.split(['-', '_']).next().unwrap_or_default();
+    ISO_639
+        .iter()
+        .find(|(short, long)| *short == primary || long.contains(&primary))
+        .map_or_else(|| primary.to_string(), |(short, _)| short.to_string())
+}
+
+pub fn same_language(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+fn matches(track: &Track, language: &str) -> bool {
+    track.language.as_deref().is_some_and(|l| same_language(l, language))
+}
+
+/// The audio track to switch to, if a preferred language is available.
+pub fn pick_audio(tracks: &[Track], prefs: &LanguagePreferences) -> Option<i64> {
+    let audio: Vec<&Track> = tracks.iter().filter(|t| t.r#type == TrackType::Audio).collect();
+    prefs.audio.iter().find_map(|language| {
+        let candidates: Vec<&&Track> = audio.iter().filter(|t| matches(t, language)).collect();
+        candidates
+            .iter()
+            .find(|t| t.default)
+            .or_else(|| candidates.first())
+            .map(|t| t.id)
+    })
+}
+
+/// The subtitle track to show, `None` meaning subtitles off. `audio_language` is the
+/// language of the selected audio track, which forced subtitles are meant to accompany.
+pub fn pick_subtitle(tracks: &[Track], prefs: &LanguagePreferences, audio_language: Option<&str>) -> Option<i64> {
+    let subs: Vec<&Track> = tracks.iter().filter(|t| t.r#type == TrackType::Subtitle).collect();
+    match prefs.subtitle_mode {
+        SubtitleMode::Off => None,
+        SubtitleMode::ForcedOnly => {
+            let forced: Vec<&&Track> = subs.iter().filter(|t| t.forced).collect();
+            audio_language
+                .into_iter()
+                .chain(prefs.subtitles.iter().map(String::as_str))
+                .find_map(|language| forced.iter().find(|t| matches(t, language)))
+                .map(|t| t.id)
+        }
+        SubtitleMode::Preferred => prefs.subtitles.iter().find_map(|language| {
+            let candidates: Vec<&&Track> = subs.iter().filter(|t| matches(t, language)).collect();
+            candidates
+                .iter()
+                .find(|t| !t.forced)
+                .or_else(|| candidates.first())
+                .map(|t| t.id)
+        }),
+    }
+}
+
+/// What to do with the subtitle track once a file has loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleChoice {
+    Select(i64),
+    Off,
+    /// No preference to act on, so mpv's own pick stands.
+    Keep,
+}
+
+pub fn choose_subtitle(tracks: &[Track], prefs: &LanguagePreferences, audio_language: Option<&str>) -> SubtitleChoice {
+    match pick_subtitle(tracks, prefs, audio_language) {
+        Some(sid) => SubtitleChoice::Select(sid),
+        // Off and forced-only must never leave mpv's default full subtitles on
+        None if prefs.subtitle_mode != SubtitleMode::Preferred || !prefs.subtitles.is_empty() => SubtitleChoice::Off,
+        None => SubtitleChoice::Keep,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // track-list of a dual-audio release as mpv reports it
+    const TRACK_LIST: &str = r#"[
+        {"id":1,"type":"video","codec":"hevc","default":true,"selected":true},
+        {"id":1,"type":"audio","lang":"jpn","codec":"flac","demux-channel-count":2,"default":true,"selected":true},
+        {"id":2,"type":"audio","lang":"eng","codec":"aac","demux-channel-count":6},
+        {"id":1,"type":"sub","lang":"eng","title":"Signs & Songs","codec":"ass","forced":true},
+        {"id":2,"type":"sub","lang":"eng","title":"Full Subtitles","codec":"ass","default":true},
+        {"id":3,"type":"sub","lang":"spa","title":"Español (España)","codec":"ass"},
+        {"id":4,"type":"sub","lang":"por","title":"Português (Brasil)","codec":"ass","forced":true}
+    ]"#;
+
+    fn tracks() -> Vec<Track> {
+        serde_json::from_str(TRACK_LIST).unwrap()
+    }
+
+    fn prefs(subtitles: &[&str], subtitle_mode: SubtitleMode) -> LanguagePreferences {
+        LanguagePreferences {
+            audio: Vec::new(),
+            subtitles: subtitles.iter().map(|l| l.to_string()).collect(),
+            subtitle_mode,
+        }
+    }
+
+    #[test]
+    fn normalizes_language_tags() {
+        assert_eq!(normalize("eng"), "en");
+        assert_eq!(normalize("en-US"), "en");
+        assert_eq!(normalize(" pt_BR "), "pt");
+        assert_eq!(normalize("GER"), "de");
+        assert_eq!(normalize("nob"), "no");
+        // Unknown codes are kept so they can still match themselves
+        assert_eq!(normalize("tlh"), "tlh");
+        assert!(same_language("fre", "fra"));
+    }
+
+    #[test]
+    fn prefers_full_subtitles() {
+        assert_eq!(pick_subtitle(&tracks(), &prefs(&["en"], SubtitleMode::Preferred), Some("jpn")), Some(2));
+    }
+
+    #[test]
+    fn falls_back_through_preference_order() {
+        assert_eq!(pick_subtitle(&tracks(), &prefs(&["fr", "es"], SubtitleMode::Preferred), Some("jpn")), Some(3));
+        assert_eq!(pick_subtitle(&tracks(), &prefs(&["fr"], SubtitleMode::Preferred), Some("jpn")), None);
+    }
+
+    #[test]
+    fn forced_only_follows_audio_language() {
+        let prefs = prefs(&["pt"], SubtitleMode::ForcedOnly);
+        assert_eq!(pick_subtitle(&tracks(), &prefs, Some("eng")), Some(1));
+        // No forced track for Japanese audio, so the preferred languages are tried next
+        assert_eq!(pick_subtitle(&tracks(), &prefs, Some("jpn")), Some(4));
+    }
+
+    #[test]
+    fn off_picks_nothing() {
+        assert_eq!(pick_subtitle(&tracks(), &prefs(&["en"], SubtitleMode::Off), Some("jpn")), None);
+    }
+
+    #[test]
+    fn chooses_what_to_do_with_subtitles() {
+        let tracks = tracks();
+        let choose = |subtitles: &[&str], mode| choose_subtitle(&tracks, &prefs(subtitles, mode), Some("jpn"));
+        assert_eq!(choose(&["en"], SubtitleMode::Preferred), SubtitleChoice::Select(2));
+        assert_eq!(choose(&["fr"], SubtitleMode::Preferred), SubtitleChoice::Off);
+        assert_eq!(choose(&[], SubtitleMode::Preferred), SubtitleChoice::Keep);
+        // The default full English track must not stay on in forced-only mode
+        assert_eq!(choose(&[], SubtitleMode::ForcedOnly), SubtitleChoice::Off);
+        assert_eq!(choose(&[], SubtitleMode::Off), SubtitleChoice::Off);
+    }
+
+    #[test]
+    fn picks_default_audio_of_preferred_language() {
+        let prefs = LanguagePreferences { audio: vec!["en".to_string()], ..Default::default() };
+        assert_eq!(pick_audio(&tracks(), &prefs), Some(2));
+        assert_eq!(pick_audio(&tracks(), &LanguagePreferences::default()), None);
+    }
+}
@@ -1,182 +1,352 @@
-use dotenv::dotenv;
-use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
 use tauri::Manager;
-use directories::ProjectDirs;
-use tokio::fs;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use raw_window_handle::{HasWindowHandle, RawWindowHandle, WindowHandle};
-
+use std::collections::BTreeMap;
+use addon::{AddonClient, AddonDescriptor, AggregatedStreams, AggregatedSubtitles, InstalledAddon, Manifest, MetaDetail};
+use catalog::{CatalogPage, CatalogRow, SearchResults};
+use series::EpisodeProgress;
+use error::AppError;
+use settings::StreamProvider;
+use storage::{LibraryItem, PlaybackSession, SeriesLanguages, WatchEvent};
+use language::LanguagePreferences;
+use stream::StreamPreferences;
+use subtitle_style::SubtitleStyle;
+
+mod addon;
+mod catalog;
+mod error;
+mod http_cache;
+mod image_cache;
+mod language;
 mod player;
 mod locale_guard;
+mod network;
+mod resume;
+mod series;
+mod settings;
+mod storage;
+mod stream;
+mod subtitle_style;
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
+#[tauri::command]
+async fn save_playback_session(state: tauri::State<'_, AppState>, session: PlaybackSession) -> Result<(), AppError> {
+    state.storage.save_playback_session(&session)
+}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct PlaybackSession {
-    imdb_id: String,
-    timestamp: u64,
-    watched: bool,
+#[tauri::command]
+async fn get_playback_session(state: tauri::State<'_, AppState>, video_id: String) -> Result<Option<PlaybackSession>, AppError> {
+    state.storage.get_playback_session(&video_id)
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-pub struct PlaybackSessions {
-    sessions: HashMap<String, PlaybackSession>,
+#[tauri::command]
+async fn get_series_progress(state: tauri::State<'_, AppState>, series_id: String) -> Result<Vec<EpisodeProgress>, AppError> {
+    let mut episodes = series::fetch_episodes(&series_id).await?;
+    let sessions = state.storage.series_sessions(&series_id)?;
+    series::apply_sessions(&mut episodes, &sessions);
+    Ok(episodes)
 }
 
-async fn get_sessions_path() -> Result<std::path::PathBuf, String> {
-    let proj_dirs = ProjectDirs::from("com", "unstrem.io", "unstrem.io")
-        .ok_or_else(|| "Could not resolve project directories".to_string())?;
-    let app_data_dir = proj_dirs.data_dir().to_path_buf();
-    let unstrem_dir = app_data_dir.join("unstrem.io");
-    fs::create_dir_all(&unstrem_dir).await.map_err(|e| e.to_string())?;
-    Ok(unstrem_dir.join("playback_sessions.json"))
+#[tauri::command]
+async fn get_next_up(state: tauri::State<'_, AppState>, series_id: String) -> Result<Option<EpisodeProgress>, AppError> {
+    let mut episodes = series::fetch_episodes(&series_id).await?;
+    let sessions = state.storage.series_sessions(&series_id)?;
+    series::apply_sessions(&mut episodes, &sessions);
+    Ok(series::next_up(&episodes, &sessions))
 }
 
-async fn load_playback_sessions(_app_handle: tauri::AppHandle) -> Result<PlaybackSessions, String> {
-    let path = get_sessions_path().await?;
-    if !path.exists() {
-        return Ok(PlaybackSessions::default());
-    }
-    let mut file = fs::File::open(&path).await.map_err(|e| e.to_string())?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).await.map_err(|e| e.to_string())?;
-    serde_json::from_str(&contents).map_err(|e| e.to_string())
+#[tauri::command]
+async fn get_watch_history(
+    state: tauri::State<'_, AppState>,
+    limit: Option<u32>,
+    before: Option<i64>,
+) -> Result<Vec<WatchEvent>, AppError> {
+    state.storage.watch_history(limit.unwrap_or(50), before)
 }
 
-async fn save_playback_sessions(_app_handle: tauri::AppHandle, sessions: &PlaybackSessions) -> Result<(), String> {
-    let path = get_sessions_path().await?;
-    let contents = serde_json::to_string_pretty(sessions).map_err(|e| e.to_string())?;
-    let mut file = fs::File::create(&path).await.map_err(|e| e.to_string())?;
-    file.write_all(contents.as_bytes()).await.map_err(|e| e.to_string())?;
-    Ok(())
+#[tauri::command]
+async fn delete_watch_event(state: tauri::State<'_, AppState>, id: i64) -> Result<(), AppError> {
+    state.storage.delete_watch_event(id)
 }
 
 #[tauri::command]
-async fn save_playback_session(app_handle: tauri::AppHandle, session: PlaybackSession) -> Result<(), String> {
-    let mut sessions = load_playback_sessions(app_handle.clone()).await?;
-    sessions.sessions.insert(session.imdb_id.clone(), session);
-    save_playback_sessions(app_handle, &sessions).await?;
-    Ok(())
+async fn clear_watch_history(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    state.storage.clear_watch_history()
 }
 
 #[tauri::command]
-async fn get_playback_session(app_handle: tauri::AppHandle, imdb_id: String) -> Result<Option<PlaybackSession>, String> {
-    let sessions = load_playback_sessions(app_handle).await?;
-    Ok(sessions.sessions.get(&imdb_id).cloned())
+async fn get_continue_watching(state: tauri::State<'_, AppState>, limit: Option<u32>) -> Result<Vec<PlaybackSession>, AppError> {
+    state.storage.continue_watching(limit.unwrap_or(20))
 }
 
 #[tauri::command]
-async fn get_trending_movies() -> Result<serde_json::Value, String> {
+async fn remove_from_continue_watching(state: tauri::State<'_, AppState>, video_id: String) -> Result<(), AppError> {
+    state.storage.dismiss_playback_session(&video_id)
+}
+
+#[tauri::command]
+async fn library_add(state: tauri::State<'_, AppState>, item: LibraryItem) -> Result<(), AppError> {
+    state.storage.library_add(&item)
+}
+
+#[tauri::command]
+async fn library_remove(state: tauri::State<'_, AppState>, imdb_id: String) -> Result<(), AppError> {
+    state.storage.library_remove(&imdb_id)
+}
+
+#[tauri::command]
+async fn get_library(state: tauri::State<'_, AppState>) -> Result<Vec<LibraryItem>, AppError> {
+    state.storage.library()
+}
+
+#[tauri::command]
+async fn get_setting(state: tauri::State<'_, AppState>, key: String) -> Result<Option<Value>, AppError> {
+    state.storage.get_setting(&key)
+}
+
+#[tauri::command]
+async fn set_setting(state: tauri::State<'_, AppState>, key: String, value: Value) -> Result<(), AppError> {
+    state.storage.set_setting(&key, &value)
+}
+
+#[tauri::command]
+async fn get_trending_movies() -> Result<serde_json::Value, AppError> {
     println!("get_trending_movies command called");
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36")
-        .build().map_err(|e| e.to_string())?;
-
-    let trending_url = "https://v3-cinemeta.strem.io/catalog/movie/top.json".to_string();
-
-    let trending_response = client.get(&trending_url).send().await.map_err(|e| {
-        eprintln!("Error fetching trending movies from Cinemeta: {}", e);
-        e.to_string()
-    })?;
-    let trending_json: Value = trending_response.json::<serde_json::Value>().await.map_err(|e| {
-        eprintln!("Error parsing Cinemeta trending movies JSON: {}", e);
-        e.to_string()
-    })?;
-
-    let mut movies_data: Vec<Value> = Vec::new();
-
-    if let Some(metas) = trending_json["metas"].as_array() {
-        for meta_item in metas {
-            let mut movie = serde_json::Map::new();
-            if let Some(imdb_id) = meta_item["imdb_id"].as_str() {
-                movie.insert("id".to_string(), Value::String(imdb_id.to_string()));
-                movie.insert("imdb_id".to_string(), Value::String(imdb_id.to_string()));
-            } else if let Some(id) = meta_item["id"].as_str() {
-                movie.insert("id".to_string(), Value::String(id.to_string()));
-                movie.insert("imdb_id".to_string(), Value::String(id.to_string()));
-            }
-            if let Some(name) = meta_item["name"].as_str() {
-                movie.insert("title".to_string(), Value::String(name.to_string()));
-            }
-            if let Some(poster) = meta_item["poster"].as_str() {
-                movie.insert("poster_path".to_string(), Value::String(poster.to_string()));
-            }
-            if let Some(banner) = meta_item["background"].as_str() {
-                movie.insert("backdrop_path".to_string(), Value::String(banner.to_string()));
-            }
-            if let Some(released) = meta_item["released"].as_str() {
-                // Use 'released' field for release_date and format it to YYYY-MM-DD
-                if let Some(date_part) = released.split('T').next() {
-                    movie.insert("release_date".to_string(), Value::String(date_part.to_string()));
-                }
-            }
-            if let Some(description) = meta_item["description"].as_str() {
-                movie.insert("overview".to_string(), Value::String(description.to_string()));
-            }
-            // Cinemeta does not provide vote_average directly in catalog, setting a placeholder
-            if let Some(imdb_rating) = meta_item["imdbRating"].as_str() {
-                if let Ok(rating) = imdb_rating.parse::<f64>() {
-                    movie.insert("vote_average".to_string(), Value::Number(serde_json::Number::from_f64(rating).unwrap()));
-                } else {
-                    movie.insert("vote_average".to_string(), Value::Number(serde_json::Number::from_f64(0.0).unwrap()));
-                }
-            } else {
-                movie.insert("vote_average".to_string(), Value::Number(serde_json::Number::from_f64(0.0).unwrap()));
-            }
+    let cinemeta = AddonClient::new(addon::http_client()?, addon::CINEMETA_URL);
+    let catalog = cinemeta.catalog("movie", "top", &[]).await?;
+
+    // The frontend still expects the TMDB-like shape it was written against
+    let movies_data: Vec<Value> = catalog
+        .metas
+        .into_iter()
+        .map(|meta_item| {
+            let id = meta_item.imdb_id.unwrap_or(meta_item.id);
+            // Use 'released' field for release_date and format it to YYYY-MM-DD
+            let release_date = meta_item
+                .released
+                .as_deref()
+                .and_then(|released| released.split('T').next())
+                .map(str::to_string);
+            // Cinemeta does not provide vote_average directly in catalog, use the IMDB rating
+            let vote_average = meta_item
+                .imdb_rating
+                .as_deref()
+                .and_then(|rating| rating.parse::<f64>().ok())
+                .unwrap_or(0.0);
+            serde_json::json!({
+                "id": id,
+                "imdb_id": id,
+                "title": meta_item.name,
+                "poster_path": meta_item.poster.as_deref().map(|url| image_cache::artwork_url(url, "w342")),
+                "backdrop_path": meta_item.background.as_deref().map(|url| image_cache::artwork_url(url, "original")),
+                "release_date": release_date,
+                "overview": meta_item.description,
+                "vote_average": vote_average,
+            })
+        })
+        .collect();
+
+    Ok(serde_json::json!({ "results": movies_data }))
+}
+
+/// Every catalog offered by the enabled add-ons, in install order.
+#[tauri::command]
+async fn get_catalogs(state: tauri::State<'_, AppState>) -> Result<Vec<CatalogRow>, AppError> {
+    let http = addon::http_client()?;
+    let addons = catalog::catalog_addons(&http, &state.storage).await?;
+    Ok(catalog::list_catalogs(&addons))
+}
+
+#[tauri::command]
+async fn get_catalog(
+    state: tauri::State<'_, AppState>,
+    transport_url: String,
+    r#type: String,
+    id: String,
+    extra: Option<BTreeMap<String, String>>,
+    cursor: Option<String>,
+) -> Result<CatalogPage, AppError> {
+    let http = addon::http_client()?;
+    let addons = catalog::catalog_addons(&http, &state.storage).await?;
+    let extra = extra.unwrap_or_default();
+    catalog::fetch_catalog(&http, &addons, &transport_url, &r#type, &id, &extra, cursor.as_deref()).await
+}
+
+#[tauri::command]
+async fn search(state: tauri::State<'_, AppState>, query: String) -> Result<SearchResults, AppError> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(SearchResults::default());
+    }
+    let http = addon::http_client()?;
+    let addons = catalog::catalog_addons(&http, &state.storage).await?;
+    Ok(catalog::search(&http, &addons, query).await)
+}
+
+/// Full details of a movie or series from Cinemeta; series episodes are in season/episode order.
+#[tauri::command]
+async fn get_meta(r#type: String, id: String) -> Result<MetaDetail, AppError> {
+    let cinemeta = AddonClient::new(addon::http_client()?, addon::CINEMETA_URL);
+    let mut meta = cinemeta.meta(&r#type, &id).await?.meta;
+    meta.videos.sort_by_key(|v| (v.season, v.episode.or(v.number)));
+    Ok(meta)
+}
+
+#[tauri::command]
+async fn get_streams(state: tauri::State<'_, AppState>, id: String, r#type: String) -> Result<AggregatedStreams, AppError> {
+    let addons = state.storage.installed_addons()?;
+    if !addons.iter().any(|a| a.enabled && a.manifest.supports("stream", &r#type, &id)) {
+        return Err(AppError::Config(format!("no installed add-on provides streams for {type} {id}")));
+    }
+    let prefs: StreamPreferences = settings::load(&state.storage, stream::PREFERENCES_KEY)?.unwrap_or_default();
+    let http = addon::http_client()?;
+    let mut aggregated = addon::aggregate_streams(&http, &addons, &r#type, &id).await;
+    stream::rank(&mut aggregated.streams, &prefs);
+    Ok(aggregated)
+}
+
+#[tauri::command]
+async fn get_subtitles(
+    state: tauri::State<'_, AppState>,
+    r#type: String,
+    id: String,
+    extra: Option<BTreeMap<String, String>>,
+) -> Result<AggregatedSubtitles, AppError> {
+    let addons = state.storage.installed_addons()?;
+    let http = addon::http_client()?;
+    let extra = extra.unwrap_or_default();
+    Ok(addon::aggregate_subtitles(&http, &addons, &r#type, &id, &extra).await)
+}
+
+#[tauri::command]
+async fn get_stream_preferences(state: tauri::State<'_, AppState>) -> Result<StreamPreferences, AppError> {
+    Ok(settings::load(&state.storage, stream::PREFERENCES_KEY)?.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_stream_preferences(state: tauri::State<'_, AppState>, preferences: StreamPreferences) -> Result<(), AppError> {
+    settings::save(&state.storage, stream::PREFERENCES_KEY, &preferences)
+}
 
-            movies_data.push(Value::Object(movie));
+#[tauri::command]
+async fn get_language_preferences(state: tauri::State<'_, AppState>) -> Result<LanguagePreferences, AppError> {
+    Ok(settings::load(&state.storage, language::PREFERENCES_KEY)?.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_language_preferences(state: tauri::State<'_, AppState>, preferences: LanguagePreferences) -> Result<(), AppError> {
+    settings::save(&state.storage, language::PREFERENCES_KEY, &preferences)
+}
+
+/// Languages remembered for a series from tracks picked while watching it.
+#[tauri::command]
+async fn get_series_languages(state: tauri::State<'_, AppState>, series_id: String) -> Result<Option<SeriesLanguages>, AppError> {
+    state.storage.series_languages(&series_id)
+}
+
+#[tauri::command]
+async fn clear_series_languages(state: tauri::State<'_, AppState>, series_id: String) -> Result<(), AppError> {
+    state.storage.clear_series_languages(&series_id)
+}
+
+#[tauri::command]
+async fn get_installed_addons(state: tauri::State<'_, AppState>) -> Result<Vec<InstalledAddon>, AppError> {
+    state.storage.installed_addons()
+}
+
+#[tauri::command]
+async fn install_addon(state: tauri::State<'_, AppState>, url: String) -> Result<Manifest, AppError> {
+    let client = AddonClient::new(addon::http_client()?, &url);
+    let manifest = client.manifest().await?;
+    state.storage.install_addon(client.base_url(), &manifest)?;
+    Ok(manifest)
+}
+
+#[tauri::command]
+async fn uninstall_addon(state: tauri::State<'_, AppState>, transport_url: String) -> Result<(), AppError> {
+    state.storage.uninstall_addon(&transport_url)
+}
+
+#[tauri::command]
+async fn set_addon_enabled(state: tauri::State<'_, AppState>, transport_url: String, enabled: bool) -> Result<(), AppError> {
+    state.storage.set_addon_enabled(&transport_url, enabled)
+}
+
+#[tauri::command]
+async fn reorder_addons(state: tauri::State<'_, AppState>, transport_urls: Vec<String>) -> Result<(), AppError> {
+    state.storage.reorder_addons(&transport_urls)
+}
+
+/// Lists add-ons offered by an add-on's `addon_catalog` resource, e.g. the ones bundled by AIOStreams.
+#[tauri::command]
+async fn get_addon_catalog(url: String, r#type: String, id: String) -> Result<Vec<AddonDescriptor>, AppError> {
+    let client = AddonClient::new(addon::http_client()?, &url);
+    Ok(client.addon_catalog(&r#type, &id).await?.addons)
+}
+
+#[tauri::command]
+fn get_network_status() -> network::NetworkStatus {
+    network::status()
+}
+
+/// Size of the add-on response cache in bytes.
+#[tauri::command]
+async fn get_http_cache_size() -> Result<u64, AppError> {
+    http_cache::size()
+}
+
+#[tauri::command]
+async fn clear_http_cache() -> Result<(), AppError> {
+    http_cache::clear()
+}
+
+/// Size of the artwork cache in bytes.
+#[tauri::command]
+async fn get_image_cache_size() -> Result<u64, AppError> {
+    image_cache::size()
+}
+
+#[tauri::command]
+async fn clear_image_cache() -> Result<(), AppError> {
+    image_cache::clear()
+}
+
+/// Cap the artwork cache, evicting least recently used images right away.
+#[tauri::command]
+async fn set_image_cache_limit(state: tauri::State<'_, AppState>, max_bytes: u64) -> Result<(), AppError> {
+    image_cache::set_max_bytes(&state.storage, max_bytes)
+}
+
+#[tauri::command]
+async fn get_stream_provider(state: tauri::State<'_, AppState>) -> Result<Option<StreamProvider>, AppError> {
+    settings::stream_provider(&state.storage)
+}
+
+/// Validates the provider against its manifest before saving; returns the manifest.
+#[tauri::command]
+async fn set_stream_provider(
+    state: tauri::State<'_, AppState>,
+    base_url: String,
+    uuid: Option<String>,
+    encrypted_password: Option<String>,
+) -> Result<Manifest, AppError> {
+    let provider = StreamProvider::new(&base_url, uuid, encrypted_password)?;
+    let manifest = provider.validate().await?;
+    // The provider is installed like any other add-on so get_streams picks it up
+    let addon_url = provider.addon_url()?;
+    if let Ok(Some(previous)) = settings::stream_provider(&state.storage) {
+        if let Ok(previous_url) = previous.addon_url() {
+            if previous_url != addon_url {
+                state.storage.uninstall_addon(&previous_url)?;
+            }
         }
     }
+    state.storage.install_addon(&addon_url, &manifest)?;
+    settings::set_stream_provider(&state.storage, &provider)?;
+    Ok(manifest)
+}
 
-    Ok(Value::Object(serde_json::Map::from_iter(vec![
-        ("results".to_string(), Value::Array(movies_data))
-    ])))
-}
-
-#[tauri::command]
-async fn get_streams(id: String, r#type: String) -> Result<serde_json::Value, String> {
-    dotenv().ok();
-    let uuid = std::env::var("AIOSTREAMS_UUID").map_err(|e| {
-        eprintln!("Error reading AIOSTREAMS_UUID: {}", e);
-        e.to_string()
-    })?;
-    let encrypted_password = std::env::var("AIOSTREAMS_ENCRYPTED_PASSWORD").map_err(|e| {
-        eprintln!("Error reading AIOSTREAMS_ENCRYPTED_PASSWORD: {}", e);
-        e.to_string()
-    })?;
-
-    let url = format!(
-        "https://aiostreams-sonic.lolcathost.ovh/stremio/{}/{}/stream/{}/{}.json",
-        uuid,
-        encrypted_password,
-        r#type,
-        id
-    );
-
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36")
-        .build().map_err(|e| e.to_string())?;
-
-    let response = client.get(&url).send().await.map_err(|e| {
-        eprintln!("Error fetching streams: {}", e);
-        e.to_string()
-    })?;
-    let text = response.text().await.map_err(|e| {
-        eprintln!("Error reading streams response body: {}", e);
-        e.to_string()
-    })?;
-    let json = serde_json::from_str(&text).map_err(|e| {
-        eprintln!("Error parsing streams JSON: {}", e);
-        e.to_string()
-    })?;
-
-    Ok(json)
-}
-
-#[tauri::command]
-fn play_video(state: tauri::State<AppState>, url: String) -> Result<(), String> {
+#[tauri::command]
+async fn play_video(state: tauri::State<'_, AppState>, url: String) -> Result<(), AppError> {
     // Deprecated in favor of HTML5 element playback; keep for compatibility but no-op
     let _ = state; let _ = url; Ok(())
 }
@@ -184,84 +354,202 @@ fn play_video(state: tauri::State<AppState>, url: String) -> Result<(), String>
 #[derive(Clone)]
 struct AppState {
     player: player::PlayerHandle,
+    storage: storage::Storage,
+}
+
+/// Player calls wait for the player thread to answer, so they run on the blocking pool
+/// rather than the main thread or an async worker.
+async fn with_player<T, F>(state: &AppState, f: F) -> Result<T, AppError>
+where
+    T: Send + 'static,
+    F: FnOnce(&player::PlayerHandle) -> Result<T, AppError> + Send + 'static,
+{
+    let player = state.player.clone();
+    tauri::async_runtime::spawn_blocking(move || f(&player))
+        .await
+        .map_err(|e| AppError::Player(e.to_string()))?
 }
 
 #[tauri::command]
-fn player_load(state: tauri::State<AppState>, url: String, start_time: Option<f64>) -> Result<(), String> {
-    state.player.load(url, start_time)
+async fn player_load(
+    state: tauri::State<'_, AppState>,
+    url: String,
+    video_id: Option<String>,
+    start_time: Option<f64>,
+) -> Result<(), AppError> {
+    // Resume from the stored checkpoint unless the caller asked for a specific position
+    let mut start_time = start_time;
+    if let (None, Some(id)) = (start_time, video_id.as_deref()) {
+        if let Some(session) = state.storage.get_playback_session(id)? {
+            if !session.watched {
+                start_time = Some(session.timestamp as f64);
+            }
+        }
+    }
+    // Waits until mpv has opened the file
+    with_player(&state, move |player| player.load(url, video_id, start_time)).await
 }
 
 #[tauri::command]
-fn player_pause_toggle(state: tauri::State<AppState>) -> Result<(), String> {
-    state.player.pause_toggle()
+async fn player_pause_toggle(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    with_player(&state, |player| player.pause_toggle()).await
 }
 
 #[tauri::command]
-fn player_stop(state: tauri::State<AppState>) -> Result<(), String> {
-    state.player.stop()
+async fn player_stop(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    with_player(&state, |player| player.stop()).await
 }
 
 #[tauri::command]
-fn player_seek_relative(state: tauri::State<AppState>, seconds: f64) -> Result<(), String> {
-    state.player.seek_relative(seconds)
+async fn player_seek_relative(state: tauri::State<'_, AppState>, seconds: f64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.seek_relative(seconds)).await
 }
 
 #[tauri::command]
-fn player_seek_absolute(state: tauri::State<AppState>, seconds: f64) -> Result<(), String> {
-    state.player.seek_absolute(seconds)
+async fn player_seek_absolute(state: tauri::State<'_, AppState>, seconds: f64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.seek_absolute(seconds)).await
 }
 
 #[tauri::command]
-fn player_position(state: tauri::State<AppState>) -> Result<f64, String> {
-    // Prevent IPC errors if player thread died; return a friendly error instead
-    state.player.position().map_err(|e| format!("player_position error: {e}"))
+async fn player_position(state: tauri::State<'_, AppState>) -> Result<f64, AppError> {
+    with_player(&state, |player| player.position()).await
 }
 
 #[tauri::command]
-fn player_duration(state: tauri::State<AppState>) -> Result<f64, String> {
-    state.player.duration().map_err(|e| format!("player_duration error: {e}"))
+async fn player_duration(state: tauri::State<'_, AppState>) -> Result<f64, AppError> {
+    with_player(&state, |player| player.duration()).await
 }
 
 #[tauri::command]
-fn player_set_volume(state: tauri::State<AppState>, volume: f64) -> Result<(), String> {
-    state.player.set_volume(volume)
+async fn player_state(state: tauri::State<'_, AppState>) -> Result<player::PlayerState, AppError> {
+    with_player(&state, |player| player.state()).await
 }
 
 #[tauri::command]
-fn player_get_volume(state: tauri::State<AppState>) -> Result<f64, String> {
-    state.player.get_volume().map_err(|e| format!("player_get_volume error: {e}"))
+async fn player_set_watched_threshold(state: tauri::State<'_, AppState>, threshold: f64) -> Result<(), AppError> {
+    let threshold = threshold.clamp(0.0, 1.0);
+    with_player(&state, move |player| player.set_watched_threshold(threshold)).await?;
+    settings::save(&state.storage, resume::WATCHED_THRESHOLD_KEY, &threshold)
 }
 
 #[tauri::command]
-fn player_cycle_audio(state: tauri::State<AppState>) -> Result<(), String> {
-    state.player.cycle_audio()
+async fn get_subtitle_style(state: tauri::State<'_, AppState>) -> Result<SubtitleStyle, AppError> {
+    Ok(settings::load(&state.storage, subtitle_style::STYLE_KEY)?.unwrap_or_default())
 }
 
+/// Applied to the player first, so values mpv rejects are not persisted.
 #[tauri::command]
-fn player_cycle_subtitle(state: tauri::State<AppState>) -> Result<(), String> {
-    state.player.cycle_subtitle()
+async fn player_set_subtitle_style(state: tauri::State<'_, AppState>, style: SubtitleStyle) -> Result<(), AppError> {
+    let applied = style.clone();
+    with_player(&state, move |player| player.set_subtitle_style(applied)).await?;
+    settings::save(&state.storage, subtitle_style::STYLE_KEY, &style)
 }
 
 #[tauri::command]
-fn player_toggle_subtitle_visibility(state: tauri::State<AppState>) -> Result<(), String> {
-    state.player.toggle_subtitle_visibility()
+async fn player_set_subtitle_delay(state: tauri::State<'_, AppState>, seconds: f64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.set_subtitle_delay(seconds)).await?;
+    let mut style: SubtitleStyle = settings::load(&state.storage, subtitle_style::STYLE_KEY)?.unwrap_or_default();
+    style.delay = seconds;
+    settings::save(&state.storage, subtitle_style::STYLE_KEY, &style)
 }
 
 #[tauri::command]
-fn player_set_audio_track(state: tauri::State<AppState>, track_id: i64) -> Result<(), String> {
-    state.player.set_audio_track(track_id)
+async fn get_audio_delay(state: tauri::State<'_, AppState>) -> Result<f64, AppError> {
+    Ok(settings::load(&state.storage, player::AUDIO_DELAY_KEY)?.unwrap_or_default())
 }
 
 #[tauri::command]
-fn player_set_subtitle_track(state: tauri::State<AppState>, track_id: i64) -> Result<(), String> {
-    state.player.set_subtitle_track(track_id)
+async fn player_set_audio_delay(state: tauri::State<'_, AppState>, seconds: f64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.set_audio_delay(seconds)).await?;
+    settings::save(&state.storage, player::AUDIO_DELAY_KEY, &seconds)
 }
 
+/// Attach an external subtitle file or URL, e.g. one picked from `get_subtitles`.
 #[tauri::command]
-fn player_screenshot(state: tauri::State<AppState>, file: String, include_subs: Option<bool>) -> Result<(), String> {
-    state.player.screenshot_to_file(file, include_subs.unwrap_or(true))
+async fn player_add_subtitle(
+    state: tauri::State<'_, AppState>,
+    url: String,
+    title: Option<String>,
+    lang: Option<String>,
+) -> Result<(), AppError> {
+    // Fetched here rather than by mpv, which would hold up the player thread during the download
+    let url = if url.starts_with("http://") || url.starts_with("https://") {
+        let path = addon::download_subtitle(&addon::http_client()?, &url).await?;
+        path.to_string_lossy().into_owned()
+    } else {
+        url
+    };
+    with_player(&state, move |player| player.add_subtitle(url, title, lang)).await
 }
 
+/// Video, audio and subtitle tracks of the current file, for building track menus.
+#[tauri::command]
+async fn player_tracks(state: tauri::State<'_, AppState>) -> Result<Vec<player::Track>, AppError> {
+    with_player(&state, |player| player.tracks()).await
+}
+
+/// Whether the player reopens the current media after recovering from a crash.
+#[tauri::command]
+async fn player_set_reload_on_restart(state: tauri::State<'_, AppState>, enabled: bool) -> Result<(), AppError> {
+    settings::save(&state.storage, player::RELOAD_ON_RESTART_KEY, &enabled)
+}
+
+#[tauri::command]
+async fn player_set_volume(state: tauri::State<'_, AppState>, volume: f64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.set_volume(volume)).await
+}
+
+#[tauri::command]
+async fn player_get_volume(state: tauri::State<'_, AppState>) -> Result<f64, AppError> {
+    with_player(&state, |player| player.get_volume()).await
+}
+
+#[tauri::command]
+async fn player_set_speed(state: tauri::State<'_, AppState>, speed: f64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.set_speed(speed)).await
+}
+
+#[tauri::command]
+async fn player_cycle_speed(state: tauri::State<'_, AppState>, backwards: Option<bool>) -> Result<f64, AppError> {
+    with_player(&state, move |player| player.cycle_speed(backwards.unwrap_or(false))).await
+}
+
+#[tauri::command]
+async fn player_frame_step(state: tauri::State<'_, AppState>, backwards: Option<bool>) -> Result<(), AppError> {
+    with_player(&state, move |player| player.frame_step(backwards.unwrap_or(false))).await
+}
+
+#[tauri::command]
+async fn player_cycle_audio(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    with_player(&state, |player| player.cycle_audio()).await
+}
+
+#[tauri::command]
+async fn player_cycle_subtitle(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    with_player(&state, |player| player.cycle_subtitle()).await
+}
+
+#[tauri::command]
+async fn player_toggle_subtitle_visibility(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    with_player(&state, |player| player.toggle_subtitle_visibility()).await
+}
+
+#[tauri::command]
+async fn player_set_audio_track(state: tauri::State<'_, AppState>, track_id: i64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.set_audio_track(track_id)).await
+}
+
+#[tauri::command]
+async fn player_set_subtitle_track(state: tauri::State<'_, AppState>, track_id: i64) -> Result<(), AppError> {
+    with_player(&state, move |player| player.set_subtitle_track(track_id)).await
+}
+
+#[tauri::command]
+async fn player_screenshot(state: tauri::State<'_, AppState>, file: String, include_subs: Option<bool>) -> Result<(), AppError> {
+    with_player(&state, move |player| player.screenshot_to_file(file, include_subs.unwrap_or(true))).await
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   // Prefer X11 backend on Linux so mpv can embed via Xlib when running under Wayland
   #[cfg(target_os = "linux")]
@@ -276,11 +564,23 @@ pub fn run() {
   }
 
   tauri::Builder::default()
+    .register_asynchronous_uri_scheme_protocol(image_cache::SCHEME, |ctx, request, responder| {
+      let Some(state) = ctx.app_handle().try_state::<AppState>() else {
+        responder.respond(tauri::http::Response::builder().status(503).body(Vec::new()).unwrap_or_default());
+        return;
+      };
+      let storage = state.storage.clone();
+      tauri::async_runtime::spawn(async move {
+        responder.respond(image_cache::serve(storage, request).await);
+      });
+    })
     .setup(|app| {
       // Ensure LC_NUMERIC is C for libraries like mpv/FFmpeg on Linux
       unsafe { locale_guard::ensure_c_numeric_locale(); }
       // Initialize and manage a single persistent player instance
-      let handle = player::spawn_player_service()?;
+      let storage = storage::Storage::open()?;
+      let handle = player::spawn_player_service(app.handle().clone(), storage.clone())?;
+      network::spawn_monitor(app.handle().clone());
       // Try to embed mpv into our main window (Tauri v2: WebviewWindow implements HasWindowHandle)
       if let Some(window) = app.get_webview_window("main") {
         if let Ok(wh) = window.window_handle() {
@@ -299,7 +599,7 @@ pub fn run() {
           }
         }
       }
-      let state = AppState { player: handle };
+      let state = AppState { player: handle, storage };
       app.manage(state);
       if cfg!(debug_assertions) {
         app.handle().plugin(
@@ -312,9 +612,46 @@ pub fn run() {
     })
     .invoke_handler(tauri::generate_handler![
       get_trending_movies,
+      get_catalogs,
+      get_catalog,
+      search,
+      get_meta,
       get_streams,
+      get_subtitles,
+      get_stream_preferences,
+      set_stream_preferences,
+      get_language_preferences,
+      set_language_preferences,
+      get_series_languages,
+      clear_series_languages,
+      get_stream_provider,
+      get_http_cache_size,
+      get_network_status,
+      clear_http_cache,
+      get_image_cache_size,
+      clear_image_cache,
+      set_image_cache_limit,
+      get_installed_addons,
+      install_addon,
+      uninstall_addon,
+      set_addon_enabled,
+      reorder_addons,
+      get_addon_catalog,
+      set_stream_provider,
       save_playback_session,
       get_playback_session,
+      get_series_progress,
+      get_next_up,
+      get_watch_history,
+      delete_watch_event,
+      clear_watch_history,
+      get_continue_watching,
+      remove_from_continue_watching,
+      library_add,
+      library_remove,
+      get_library,
+      get_setting,
+      set_setting,
       play_video,
       player_load,
       player_pause_toggle,
@@ -323,13 +660,26 @@ pub fn run() {
       player_seek_absolute,
       player_position,
       player_duration,
+      player_state,
+      player_tracks,
+      player_set_watched_threshold,
+      player_set_reload_on_restart,
       player_set_volume,
       player_get_volume,
+      player_set_speed,
+      player_cycle_speed,
+      player_frame_step,
       player_cycle_audio,
       player_cycle_subtitle,
       player_toggle_subtitle_visibility,
       player_set_audio_track,
       player_set_subtitle_track,
+      get_subtitle_style,
+      player_set_subtitle_style,
+      player_set_subtitle_delay,
+      get_audio_delay,
+      player_set_audio_delay,
+      player_add_subtitle,
       player_screenshot,
     ])
     .run(tauri::generate_context!())
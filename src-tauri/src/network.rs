@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+use crate::addon;
+use crate::http_cache;
+
+pub const EVENT_STATUS: &str = "network-status";
+
+// While offline, how often connectivity is probed; while online, how often failed work is retried
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static APP: OnceLock<AppHandle> = OnceLock::new();
+// Set when a host could not be reached, so the monitor probes right away
+static SUSPECT: AtomicBool = AtomicBool::new(false);
+
+fn wake_monitor() -> &'static Notify {
+    static WAKE: OnceLock<Notify> = OnceLock::new();
+    WAKE.get_or_init(Notify::new)
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum NetworkStatus {
+    Online,
+    Offline,
+}
+
+pub fn status() -> NetworkStatus {
+    if is_offline() {
+        NetworkStatus::Offline
+    } else {
+        NetworkStatus::Online
+    }
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Only failures to reach the host count; an add-on answering with an error status is reachable.
+pub fn is_offline_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout()
+}
+
+/// A single host being unreachable says little about the network (a dead add-on, a DNS
+/// failure), so this only asks the monitor to probe; the probe decides whether we are offline.
+pub fn report_unreachable() {
+    SUSPECT.store(true, Ordering::Relaxed);
+    wake_monitor().notify_one();
+}
+
+async fn probe(http: &reqwest::Client) -> bool {
+    http.head(format!("{}/manifest.json", addon::CINEMETA_URL))
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .is_ok()
+}
+
+/// Update the connectivity state, notifying the UI when it changes.
+pub fn set_offline(offline: bool) {
+    if OFFLINE.swap(offline, Ordering::Relaxed) == offline {
+        return;
+    }
+    eprintln!("Network is {}", if offline { "offline" } else { "back online" });
+    if let Some(app) = APP.get() {
+        if let Err(e) = app.emit(EVENT_STATUS, status()) {
+            eprintln!("network status emit error: {e}");
+        }
+    }
+}
+
+/// Probes connectivity after a host could not be reached and while offline, and retries
+/// requests that failed because of it.
+pub fn spawn_monitor(app: AppHandle) {
+    let _ = APP.set(app);
+    tauri::async_runtime::spawn(async {
+        loop {
+            let woken = tokio::select! {
+                _ = tokio::time::sleep(PROBE_INTERVAL) => false,
+                _ = wake_monitor().notified() => true,
+            };
+            let Ok(http) = addon::http_client() else {
+                continue;
+            };
+            if SUSPECT.swap(false, Ordering::Relaxed) || is_offline() {
+                let online = probe(&http).await;
+                set_offline(!online);
+                if !online {
+                    continue;
+                }
+            }
+            // Retrying right after a failure would just hit the same dead host again
+            if !woken {
+                http_cache::retry_pending(&http).await;
+            }
+        }
+    });
+}
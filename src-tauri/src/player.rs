@@ -1,14 +1,186 @@
-use libmpv2::Mpv;
+use libmpv2::events::{Event, PropertyData};
+use libmpv2::{Format, Mpv};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::AppError;
+use crate::language::{self, LanguagePreferences, SubtitleChoice};
+use crate::resume::ResumeTracker;
+use crate::settings;
+use crate::storage::{self, SeriesLanguages, Storage};
+use crate::subtitle_style::{self, SubtitleStyle};
+
+/// Emitted with a [`PlayerState`] snapshot whenever an observed property changes.
+pub const EVENT_STATE: &str = "player-state";
+/// Emitted with the [`Track`] list whenever tracks are added, removed or selected.
+pub const EVENT_TRACK_LIST: &str = "player-track-list";
+/// Emitted with a [`PlayerRestarted`] after the supervisor recreated a dead player.
+pub const EVENT_RESTARTED: &str = "player-restarted";
+/// Whether a restarted player reopens the media that was playing; defaults to true.
+pub const RELOAD_ON_RESTART_KEY: &str = "player_reload_on_restart";
+/// Audio delay in seconds, for fixing lip-sync; positive delays the audio.
+pub const AUDIO_DELAY_KEY: &str = "audio_delay";
+
+// How long the player thread waits for a command before draining mpv events
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// time-pos changes every frame; don't flood the webview with IPC messages
+const POSITION_EMIT_INTERVAL: Duration = Duration::from_millis(250);
+// Stops for cycling the playback speed
+const SPEED_STEPS: &[f64] = &[0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4.0;
+const RESTART_ATTEMPTS: u32 = 3;
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+// A player dying again this soon after a restart is likely choking on the media itself
+const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(10);
+// Longest a `load` waits for mpv to open the file before giving up on it
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlayerState {
+    pub time_pos: Option<f64>,
+    pub duration: Option<f64>,
+    pub paused: bool,
+    pub eof_reached: bool,
+    pub idle: bool,
+    pub speed: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackType {
+    Video,
+    Audio,
+    #[serde(rename = "sub")]
+    Subtitle,
+}
+
+/// An entry of mpv's `track-list`; `id` is what `player_set_audio_track` and friends take.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track {
+    pub id: i64,
+    pub r#type: TrackType,
+    #[serde(default, alias = "lang")]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub codec: Option<String>,
+    #[serde(default, alias = "demux-channel-count")]
+    pub channels: Option<u32>,
+    #[serde(default)]
+    pub default: bool,
+    #[serde(default)]
+    pub forced: bool,
+    #[serde(default)]
+    pub external: bool,
+    #[serde(default)]
+    pub selected: bool,
+}
+
+// Only episodes carry per-series language choices
+fn series_id(video_id: &str) -> Option<&str> {
+    match storage::parse_video_id(video_id) {
+        (series_id, Some(_)) => Some(series_id),
+        _ => None,
+    }
+}
+
+fn parse_tracks(json: &str) -> Result<Vec<Track>, AppError> {
+    Ok(serde_json::from_str(json)?)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerRestarted {
+    pub reason: String,
+    /// Whether the media that was playing has been reopened.
+    pub reloaded: bool,
+}
+
+/// A subtitle file or URL attached to the current file with `sub-add`.
+#[derive(Debug, Clone)]
+pub struct ExternalSubtitle {
+    pub url: String,
+    pub title: Option<String>,
+    pub lang: Option<String>,
+}
+
+/// Track selection re-applied once a reopened file has loaded.
+struct TrackRestore {
+    audio_track: Option<i64>,
+    subtitle_track: Option<i64>,
+    subtitles: Vec<ExternalSubtitle>,
+}
+
+/// The file mpv is playing, kept so a restarted player can reopen it.
+#[derive(Debug, Clone)]
+struct Media {
+    url: String,
+    video_id: Option<String>,
+    position: Option<f64>,
+}
+
+/// The parts of an mpv event the player acts on, owned so the event no longer borrows `Mpv`.
+enum PlayerEvent {
+    PropertyChange(String, PropertyValue),
+    StartFile,
+    FileLoaded,
+    EndFile,
+    Shutdown,
+    Other,
+}
+
+enum PropertyValue {
+    Str(String),
+    Flag(bool),
+    Double(f64),
+    Other,
+}
+
+impl From<Event<'_>> for PlayerEvent {
+    fn from(event: Event<'_>) -> Self {
+        match event {
+            Event::PropertyChange { name, change, .. } => {
+                let value = match change {
+                    PropertyData::Str(s) => PropertyValue::Str(s.to_string()),
+                    PropertyData::Flag(v) => PropertyValue::Flag(v),
+                    PropertyData::Double(v) => PropertyValue::Double(v),
+                    _ => PropertyValue::Other,
+                };
+                PlayerEvent::PropertyChange(name.to_string(), value)
+            }
+            Event::StartFile => PlayerEvent::StartFile,
+            Event::FileLoaded => PlayerEvent::FileLoaded,
+            Event::EndFile(_) => PlayerEvent::EndFile,
+            Event::Shutdown => PlayerEvent::Shutdown,
+            _ => PlayerEvent::Other,
+        }
+    }
+}
 
 pub struct VideoPlayer {
     mpv: Mpv,
+    state: PlayerState,
+    last_state_emit: Instant,
+    resume: ResumeTracker,
+    storage: Storage,
+    pending_load: Option<PendingLoad>,
+    // Checked against the track list once the loading file has opened
+    languages: Option<LanguagePreferences>,
+    // Promoted to `media` on start-file, like the resume tracker's queue
+    loading: Option<Media>,
+    media: Option<Media>,
+    restore_tracks: Option<TrackRestore>,
+    shut_down: bool,
 }
 
 impl VideoPlayer {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
+    pub fn new(storage: Storage) -> Result<Self, Box<dyn Error>> {
         let mpv = Mpv::new()?;
         let _ = mpv.set_property("hwdec", "auto-safe");
         let _ = mpv.set_property("cache", "yes");
@@ -19,31 +191,282 @@ impl VideoPlayer {
         let _ = mpv.set_property("keep-open", "no");
         let _ = mpv.set_property("force-window", "no");
         let _ = mpv.set_property("vo", "gpu");
-        Ok(Self { mpv })
+        // Keep voices at their natural pitch when playing faster or slower
+        let _ = mpv.set_property("audio-pitch-correction", true);
+
+        mpv.disable_deprecated_events()?;
+        mpv.observe_property("time-pos", Format::Double, 0)?;
+        mpv.observe_property("duration", Format::Double, 0)?;
+        mpv.observe_property("pause", Format::Flag, 0)?;
+        mpv.observe_property("eof-reached", Format::Flag, 0)?;
+        mpv.observe_property("idle-active", Format::Flag, 0)?;
+        mpv.observe_property("speed", Format::Double, 0)?;
+        // Node properties are delivered as JSON when observed as strings
+        mpv.observe_property("track-list", Format::String, 0)?;
+
+        let player = Self {
+            mpv,
+            state: PlayerState { speed: 1.0, ..Default::default() },
+            last_state_emit: Instant::now(),
+            resume: ResumeTracker::new(storage.clone()),
+            storage,
+            pending_load: None,
+            languages: None,
+            loading: None,
+            media: None,
+            restore_tracks: None,
+            shut_down: false,
+        };
+        player.apply_settings();
+        Ok(player)
     }
 
-    fn load(&self, url: &str, start_time: Option<f64>) -> Result<(), Box<dyn Error>> {
-        if let Some(t) = start_time {
-            let _ = self.mpv.set_property("start", t);
+    /// Apply the persisted subtitle style and audio delay; bad values are logged and skipped.
+    fn apply_settings(&self) {
+        match settings::load::<SubtitleStyle>(&self.storage, subtitle_style::STYLE_KEY) {
+            Ok(Some(style)) => respond(None, self.set_subtitle_style(&style), "subtitle style"),
+            Ok(None) => {}
+            Err(e) => eprintln!("subtitle style error: {e}"),
+        }
+        match settings::load::<f64>(&self.storage, AUDIO_DELAY_KEY) {
+            Ok(Some(delay)) => respond(None, self.set_audio_delay(delay), "audio delay"),
+            Ok(None) => {}
+            Err(e) => eprintln!("audio delay error: {e}"),
         }
-        self.mpv.command("loadfile", &[url])?;
-        Ok(())
     }
 
-    fn pause_toggle(&self) -> Result<(), Box<dyn Error>> {
+    /// Drain pending mpv events without blocking and forward changes to the webview.
+    fn drain_events(&mut self, app: &AppHandle) {
+        let mut changed = false;
+        let mut position_changed = false;
+        // Copied out of mpv's buffer right away, since handling an event needs `self` again
+        while let Some(ev) = self.mpv.wait_event(0.0).map(|ev| ev.map(PlayerEvent::from)) {
+            match ev {
+                Ok(PlayerEvent::PropertyChange(name, change)) => match (name.as_str(), change) {
+                    ("time-pos", PropertyValue::Double(v)) => {
+                        self.state.time_pos = Some(v);
+                        if let Some(media) = self.media.as_mut() {
+                            media.position = Some(v);
+                        }
+                        position_changed = true;
+                        self.resume.tick(&self.state);
+                    }
+                    ("duration", PropertyValue::Double(v)) => {
+                        self.state.duration = Some(v);
+                        changed = true;
+                    }
+                    ("pause", PropertyValue::Flag(v)) => {
+                        if self.state.paused != v {
+                            self.resume.pause_changed(v, &self.state);
+                        }
+                        self.state.paused = v;
+                        changed = true;
+                    }
+                    ("eof-reached", PropertyValue::Flag(v)) => {
+                        self.state.eof_reached = v;
+                        changed = true;
+                    }
+                    ("idle-active", PropertyValue::Flag(v)) => {
+                        self.state.idle = v;
+                        changed = true;
+                    }
+                    ("speed", PropertyValue::Double(v)) => {
+                        self.state.speed = v;
+                        changed = true;
+                    }
+                    ("track-list", PropertyValue::Str(json)) => match parse_tracks(&json) {
+                        Ok(tracks) => {
+                            let _ = app.emit(EVENT_TRACK_LIST, tracks);
+                        }
+                        Err(e) => eprintln!("track-list parse error: {e}"),
+                    },
+                    _ => {}
+                },
+                Ok(PlayerEvent::StartFile) => {
+                    if let Some(pending) = self.pending_load.as_mut() {
+                        pending.started = true;
+                    }
+                    self.media = self.loading.take();
+                    self.resume.start_file();
+                    self.state.time_pos = None;
+                    self.state.duration = None;
+                    changed = true;
+                }
+                Ok(PlayerEvent::FileLoaded) => {
+                    if let Some(pending) = self.pending_load.take() {
+                        let _ = pending.reply.send(Ok(()));
+                    }
+                    if let Some(prefs) = self.languages.take() {
+                        self.apply_languages(&prefs);
+                    }
+                    if let Some(restore) = self.restore_tracks.take() {
+                        // Re-added in the original order so external tracks get their old ids back
+                        for subtitle in &restore.subtitles {
+                            respond(None, self.add_subtitle(subtitle, false), "restore sub-add");
+                        }
+                        if let Some(aid) = restore.audio_track {
+                            respond(None, self.set_audio_track(aid), "restore aid");
+                        }
+                        if let Some(sid) = restore.subtitle_track {
+                            respond(None, self.set_subtitle_track(sid), "restore sid");
+                        }
+                    }
+                }
+                Ok(PlayerEvent::EndFile) => {
+                    self.fail_pending_load(AppError::Player("playback ended before the file was loaded".to_string()));
+                    self.media = None;
+                    self.resume.end_file(&self.state);
+                    // time-pos/duration become unavailable and mpv stops reporting them
+                    self.state.time_pos = None;
+                    self.state.duration = None;
+                    changed = true;
+                }
+                Ok(PlayerEvent::Shutdown) => self.shut_down = true,
+                Ok(PlayerEvent::Other) => {}
+                // libmpv2 reports an end-file carrying an error (bad URL, unsupported format) as Err
+                Err(e) => {
+                    eprintln!("mpv event error: {e}");
+                    self.media = None;
+                    self.fail_pending_load(AppError::Player(e.to_string()));
+                }
+            }
+        }
+        let throttled = self.last_state_emit.elapsed() < POSITION_EMIT_INTERVAL;
+        if changed || (position_changed && !throttled) {
+            let _ = app.emit(EVENT_STATE, self.state.clone());
+            self.last_state_emit = Instant::now();
+        }
+    }
+
+    fn load(&mut self, url: &str, video_id: Option<String>, start_time: Option<f64>, reply: Option<Reply<()>>) {
+        if let Some(previous) = self.pending_load.take() {
+            let _ = previous.reply.send(Err(AppError::Player("replaced by another load".to_string())));
+        }
+        // Save where we were in the previous item before it gets replaced
+        self.resume.queue(video_id.clone(), start_time, &self.state);
+        let prefs = self.language_preferences(video_id.as_deref());
+        // mpv picks by alang/slang itself; the track list is checked again on file-loaded
+        // for tags it does not match (`eng` vs `en`) and for forced-only subtitles
+        let _ = self.mpv.set_property("alang", prefs.audio.join(","));
+        let _ = self.mpv.set_property("slang", prefs.subtitles.join(","));
+        let _ = self.mpv.set_property("aid", "auto");
+        let sid = if prefs.subtitle_mode == language::SubtitleMode::Off { "no" } else { "auto" };
+        let _ = self.mpv.set_property("sid", sid);
+        self.languages = Some(prefs);
+        self.loading = Some(Media { url: url.to_string(), video_id, position: start_time });
+        match start_time {
+            Some(t) => { let _ = self.mpv.set_property("start", t); }
+            // `start` is sticky across loads; clear a previous resume position
+            None => { let _ = self.mpv.set_property("start", "none"); }
+        }
+        // loadfile only queues the file; whether it opens is known once mpv reports back
+        let result = self.mpv.command("loadfile", &[url]);
+        if result.is_err() {
+            self.loading = None;
+        }
+        match (result, reply) {
+            (Ok(()), Some(reply)) => self.pending_load = Some(PendingLoad { reply, started: false }),
+            (result, reply) => respond(reply, result.map_err(AppError::from), "load"),
+        }
+    }
+
+    /// Global language preferences, with what was last picked for this series in front.
+    fn language_preferences(&self, video_id: Option<&str>) -> LanguagePreferences {
+        let prefs: LanguagePreferences = match settings::load(&self.storage, language::PREFERENCES_KEY) {
+            Ok(prefs) => prefs.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("language preferences error: {e}");
+                LanguagePreferences::default()
+            }
+        };
+        let series = video_id.and_then(series_id).and_then(|id| self.storage.series_languages(id).ok().flatten());
+        match series {
+            Some(series) => prefs.with_override(&series),
+            None => prefs,
+        }
+    }
+
+    fn apply_languages(&self, prefs: &LanguagePreferences) {
+        let tracks = match self.tracks() {
+            Ok(tracks) => tracks,
+            Err(e) => {
+                eprintln!("track-list error: {e}");
+                return;
+            }
+        };
+        let aid = language::pick_audio(&tracks, prefs);
+        if let Some(aid) = aid {
+            respond(None, self.set_audio_track(aid), "language aid");
+        }
+        let audio = tracks
+            .iter()
+            .find(|t| t.r#type == TrackType::Audio && aid.map_or(t.selected, |id| t.id == id))
+            .and_then(|t| t.language.as_deref());
+        match language::choose_subtitle(&tracks, prefs, audio) {
+            SubtitleChoice::Select(sid) => respond(None, self.set_subtitle_track(sid), "language sid"),
+            SubtitleChoice::Off => respond(None, self.mpv.set_property("sid", "no").map_err(AppError::from), "language sid"),
+            SubtitleChoice::Keep => {}
+        }
+    }
+
+    /// Remember the languages picked by hand for the rest of the series.
+    fn remember_languages(&self) {
+        let Some(series_id) = self.media.as_ref().and_then(|m| m.video_id.as_deref()).and_then(series_id) else {
+            return;
+        };
+        let Ok(tracks) = self.tracks() else {
+            return;
+        };
+        let selected = |kind: TrackType| tracks.iter().find(|t| t.r#type == kind && t.selected);
+        let subtitle = selected(TrackType::Subtitle);
+        let languages = SeriesLanguages {
+            audio: selected(TrackType::Audio).and_then(|t| t.language.clone()),
+            // Forced tracks follow the global mode rather than becoming the series' subtitles
+            subtitle: subtitle.filter(|t| !t.forced).and_then(|t| t.language.clone()),
+            subtitles_off: subtitle.is_none(),
+        };
+        if let Err(e) = self.storage.set_series_languages(series_id, &languages) {
+            eprintln!("series languages error: {e}");
+        }
+    }
+
+    fn remembering(&self, result: Result<(), AppError>) -> Result<(), AppError> {
+        if result.is_ok() {
+            self.remember_languages();
+        }
+        result
+    }
+
+    /// Answer a waiting `load` once its file failed before it opened.
+    fn fail_pending_load(&mut self, error: AppError) {
+        if self.pending_load.as_ref().is_some_and(|p| p.started) {
+            self.abort_pending_load(error);
+        }
+    }
+
+    /// Answer a waiting `load` whether or not mpv has started the file yet.
+    fn abort_pending_load(&mut self, error: AppError) {
+        if let Some(pending) = self.pending_load.take() {
+            let _ = pending.reply.send(Err(error));
+        }
+    }
+
+    fn pause_toggle(&self) -> Result<(), AppError> {
         let paused: bool = self.mpv.get_property("pause")?;
         self.mpv.set_property("pause", !paused)?;
         Ok(())
     }
 
-    fn stop(&self) -> Result<(), Box<dyn Error>> {
+    fn stop(&mut self) -> Result<(), AppError> {
+        self.resume.stop(&self.state);
+        self.loading = None;
         self.mpv.command("stop", &[])?;
         // Reset properties that may affect next load
         let _ = self.mpv.set_property("pause", false);
         Ok(())
     }
 
-    fn set_wid(&self, wid: i64) -> Result<(), Box<dyn Error>> {
+    fn set_wid(&self, wid: i64) -> Result<(), AppError> {
         // Embed into the provided native window handle when supported
         // Must be set before loadfile; set vo explicitly.
         self.mpv.set_property("vo", "gpu")?;
@@ -51,86 +474,396 @@ impl VideoPlayer {
         Ok(())
     }
 
-    fn seek_relative(&self, seconds: f64) -> Result<(), Box<dyn Error>> {
-        self.mpv.command("seek", &[&seconds.to_string(), "relative", "exact"]) ?;
+    fn seek_relative(&self, seconds: f64) -> Result<(), AppError> {
+        self.mpv.command("seek", &[&seconds.to_string(), "relative", "exact"])?;
         Ok(())
     }
 
-    fn seek_absolute(&self, seconds: f64) -> Result<(), Box<dyn Error>> {
+    fn seek_absolute(&self, seconds: f64) -> Result<(), AppError> {
         self.mpv.set_property("time-pos", seconds)?;
         Ok(())
     }
 
-    fn position(&self) -> Result<f64, Box<dyn Error>> {
+    fn position(&self) -> Result<f64, AppError> {
         let pos: f64 = self.mpv.get_property("time-pos")?;
         Ok(pos)
     }
 
-    fn duration(&self) -> Result<f64, Box<dyn Error>> {
+    fn duration(&self) -> Result<f64, AppError> {
         let dur: f64 = self.mpv.get_property("duration")?;
         Ok(dur)
     }
 
-    fn set_volume(&self, volume: f64) -> Result<(), Box<dyn Error>> {
+    fn set_volume(&self, volume: f64) -> Result<(), AppError> {
         self.mpv.set_property("volume", volume)?;
         Ok(())
     }
 
-    fn get_volume(&self) -> Result<f64, Box<dyn Error>> {
+    fn get_volume(&self) -> Result<f64, AppError> {
         let vol: f64 = self.mpv.get_property("volume")?;
         Ok(vol)
     }
 
-    fn cycle_audio(&self) -> Result<(), Box<dyn Error>> {
-        self.mpv.command("cycle", &["audio"]) ?;
+    fn cycle_audio(&self) -> Result<(), AppError> {
+        self.mpv.command("cycle", &["audio"])?;
+        Ok(())
+    }
+
+    fn set_speed(&self, speed: f64) -> Result<(), AppError> {
+        self.mpv.set_property("speed", speed.clamp(MIN_SPEED, MAX_SPEED))?;
         Ok(())
     }
 
-    fn cycle_subtitle(&self) -> Result<(), Box<dyn Error>> {
-        self.mpv.command("cycle", &["sub"]) ?;
+    /// Step to the next (or previous) speed in [`SPEED_STEPS`], wrapping around.
+    fn cycle_speed(&self, backwards: bool) -> Result<f64, AppError> {
+        let current: f64 = self.mpv.get_property("speed")?;
+        let next = if backwards {
+            SPEED_STEPS.iter().rev().find(|s| **s < current - f64::EPSILON).or(SPEED_STEPS.last())
+        } else {
+            SPEED_STEPS.iter().find(|s| **s > current + f64::EPSILON).or(SPEED_STEPS.first())
+        };
+        let next = next.copied().unwrap_or(1.0);
+        self.mpv.set_property("speed", next)?;
+        Ok(next)
+    }
+
+    /// Show the next or previous frame; mpv pauses playback to do so.
+    fn frame_step(&self, backwards: bool) -> Result<(), AppError> {
+        let command = if backwards { "frame-back-step" } else { "frame-step" };
+        self.mpv.command(command, &[])?;
         Ok(())
     }
 
-    fn toggle_subtitle_visibility(&self) -> Result<(), Box<dyn Error>> {
+    fn cycle_subtitle(&self) -> Result<(), AppError> {
+        self.mpv.command("cycle", &["sub"])?;
+        Ok(())
+    }
+
+    fn toggle_subtitle_visibility(&self) -> Result<(), AppError> {
         let vis: bool = self.mpv.get_property("sub-visibility")?;
         self.mpv.set_property("sub-visibility", !vis)?;
         Ok(())
     }
 
-    fn set_audio_track(&self, track_id: i64) -> Result<(), Box<dyn Error>> {
+    fn set_audio_track(&self, track_id: i64) -> Result<(), AppError> {
         self.mpv.set_property("aid", track_id)?;
         Ok(())
     }
 
-    fn set_subtitle_track(&self, track_id: i64) -> Result<(), Box<dyn Error>> {
+    fn set_subtitle_track(&self, track_id: i64) -> Result<(), AppError> {
         self.mpv.set_property("sid", track_id)?;
         Ok(())
     }
 
-    fn screenshot_to_file(&self, file: &str, include_subs: bool) -> Result<(), Box<dyn Error>> {
+    fn set_subtitle_style(&self, style: &SubtitleStyle) -> Result<(), AppError> {
+        self.mpv.set_property("sub-delay", style.delay)?;
+        self.mpv.set_property("sub-font-size", style.font_size as i64)?;
+        self.mpv.set_property("sub-scale", style.scale)?;
+        self.mpv.set_property("sub-pos", style.position as i64)?;
+        self.mpv.set_property("sub-color", style.color.as_str())?;
+        self.mpv.set_property("sub-border-size", style.border_size)?;
+        self.mpv.set_property("sub-border-color", style.border_color.as_str())?;
+        match &style.background {
+            Some(color) => {
+                self.mpv.set_property("sub-back-color", color.as_str())?;
+                self.mpv.set_property("sub-border-style", "background-box")?;
+            }
+            None => {
+                self.mpv.set_property("sub-back-color", "#00000000")?;
+                self.mpv.set_property("sub-border-style", "outline-and-shadow")?;
+            }
+        }
+        self.mpv.set_property("sub-ass-override", style.ass_override.as_mpv())?;
+        Ok(())
+    }
+
+    fn set_subtitle_delay(&self, seconds: f64) -> Result<(), AppError> {
+        self.mpv.set_property("sub-delay", seconds)?;
+        Ok(())
+    }
+
+    fn set_audio_delay(&self, seconds: f64) -> Result<(), AppError> {
+        self.mpv.set_property("audio-delay", seconds)?;
+        Ok(())
+    }
+
+    fn add_subtitle(&self, subtitle: &ExternalSubtitle, select: bool) -> Result<(), AppError> {
+        let flag = if select { "select" } else { "auto" };
+        let mut args = vec![subtitle.url.as_str(), flag];
+        // sub-add takes the title before the language, so fall back to the language as title
+        if let Some(title) = subtitle.title.as_deref().or(subtitle.lang.as_deref()) {
+            args.push(title);
+            if let Some(lang) = subtitle.lang.as_deref() {
+                args.push(lang);
+            }
+        }
+        self.mpv.command("sub-add", &args)?;
+        Ok(())
+    }
+
+    fn screenshot_to_file(&self, file: &str, include_subs: bool) -> Result<(), AppError> {
         let mode = if include_subs { "subtitles" } else { "video" };
         self.mpv.command("screenshot-to-file", &[file, mode])?;
         Ok(())
     }
+
+    fn tracks(&self) -> Result<Vec<Track>, AppError> {
+        // Node properties come back as JSON when read as strings
+        let json: String = self.mpv.get_property("track-list")?;
+        parse_tracks(&json)
+    }
+
+    fn track(&self, name: &str) -> Option<i64> {
+        self.mpv.get_property(name).ok()
+    }
+
+    /// Serve commands until the handle is dropped or mpv shuts down.
+    fn run(&mut self, app: &AppHandle, rx: &Receiver<PlayerCommand>, session: &mut Session) -> Exit {
+        while !self.shut_down {
+            match rx.recv_timeout(EVENT_POLL_INTERVAL) {
+                Ok(cmd) => {
+                    session.record(&cmd);
+                    // These pick tracks on mpv's side; read back what ended up selected
+                    let cycled = matches!(
+                        cmd,
+                        PlayerCommand::CycleAudio { .. }
+                            | PlayerCommand::CycleSubtitle { .. }
+                            | PlayerCommand::AddSubtitle { .. }
+                    );
+                    self.handle(cmd);
+                    if cycled {
+                        session.audio_track = self.track("aid");
+                        session.subtitle_track = self.track("sid");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Exit::Disconnected,
+            }
+            self.drain_events(app);
+            session.media = self.media.clone().or_else(|| self.loading.clone());
+            session.speed = Some(self.state.speed).filter(|s| *s > 0.0);
+        }
+        Exit::Shutdown
+    }
+
+    fn handle(&mut self, cmd: PlayerCommand) {
+        match cmd {
+            PlayerCommand::Load { url, video_id, start_time, reply } => self.load(&url, video_id, start_time, reply),
+            PlayerCommand::SetWid { wid, reply } => respond(reply, self.set_wid(wid), "set wid"),
+            PlayerCommand::PauseToggle { reply } => respond(reply, self.pause_toggle(), "pause"),
+            PlayerCommand::Stop { reply } => {
+                let result = self.stop();
+                self.abort_pending_load(AppError::Player("stopped before the file was loaded".to_string()));
+                respond(reply, result, "stop");
+            }
+            PlayerCommand::SeekRelative { seconds, reply } => respond(reply, self.seek_relative(seconds), "seek rel"),
+            PlayerCommand::SeekAbsolute { seconds, reply } => respond(reply, self.seek_absolute(seconds), "seek abs"),
+            PlayerCommand::SetVolume { volume, reply } => respond(reply, self.set_volume(volume), "volume"),
+            PlayerCommand::GetVolume { reply } => { let _ = reply.send(self.get_volume()); }
+            PlayerCommand::Position { reply } => { let _ = reply.send(self.position()); }
+            PlayerCommand::Duration { reply } => { let _ = reply.send(self.duration()); }
+            PlayerCommand::State { reply } => { let _ = reply.send(Ok(self.state.clone())); }
+            PlayerCommand::Tracks { reply } => { let _ = reply.send(self.tracks()); }
+            PlayerCommand::SetWatchedThreshold { threshold, reply } => {
+                self.resume.set_watched_threshold(threshold);
+                respond(reply, Ok(()), "watched threshold");
+            }
+            PlayerCommand::SetSpeed { speed, reply } => respond(reply, self.set_speed(speed), "speed"),
+            PlayerCommand::CycleSpeed { backwards, reply } => { let _ = reply.send(self.cycle_speed(backwards)); }
+            PlayerCommand::FrameStep { backwards, reply } => respond(reply, self.frame_step(backwards), "frame step"),
+            PlayerCommand::CycleAudio { reply } => respond(reply, self.remembering(self.cycle_audio()), "cycle audio"),
+            PlayerCommand::CycleSubtitle { reply } => {
+                respond(reply, self.remembering(self.cycle_subtitle()), "cycle sub")
+            }
+            PlayerCommand::ToggleSubtitleVisibility { reply } => {
+                respond(reply, self.toggle_subtitle_visibility(), "toggle sub vis")
+            }
+            PlayerCommand::SetAudioTrack { track_id, reply } => {
+                respond(reply, self.remembering(self.set_audio_track(track_id)), "set aid")
+            }
+            PlayerCommand::SetSubtitleTrack { track_id, reply } => {
+                respond(reply, self.remembering(self.set_subtitle_track(track_id)), "set sid")
+            }
+            PlayerCommand::SetSubtitleStyle { style, reply } => {
+                respond(reply, self.set_subtitle_style(&style), "subtitle style")
+            }
+            PlayerCommand::SetSubtitleDelay { seconds, reply } => {
+                respond(reply, self.set_subtitle_delay(seconds), "sub delay")
+            }
+            PlayerCommand::SetAudioDelay { seconds, reply } => respond(reply, self.set_audio_delay(seconds), "audio delay"),
+            PlayerCommand::AddSubtitle { subtitle, select, reply } => {
+                respond(reply, self.add_subtitle(&subtitle, select), "sub-add")
+            }
+            PlayerCommand::Screenshot { file, include_subs, reply } => {
+                respond(reply, self.screenshot_to_file(&file, include_subs), "screenshot")
+            }
+        }
+    }
+}
+
+type Reply<T> = Sender<Result<T, AppError>>;
+
+/// A `load` waiting for mpv to open or reject its file.
+struct PendingLoad {
+    reply: Reply<()>,
+    // Set on start-file, so the end-file of the file being replaced is not taken as a failure
+    started: bool,
+}
+
+// Without a reply channel the error has nowhere to go but the log
+fn respond<T>(reply: Option<Reply<T>>, result: Result<T, AppError>, what: &str) {
+    match reply {
+        Some(reply) => {
+            let _ = reply.send(result);
+        }
+        None => {
+            if let Err(e) = result {
+                eprintln!("{what} error: {e}");
+            }
+        }
+    }
+}
+
+enum Exit {
+    Disconnected,
+    Shutdown,
+}
+
+/// What the supervisor re-applies to a recreated player. Lives outside [`VideoPlayer`]
+/// so it survives a panic on the player thread.
+#[derive(Default)]
+struct Session {
+    wid: Option<i64>,
+    volume: Option<f64>,
+    speed: Option<f64>,
+    audio_track: Option<i64>,
+    subtitle_track: Option<i64>,
+    subtitles: Vec<ExternalSubtitle>,
+    media: Option<Media>,
+}
+
+impl Session {
+    fn record(&mut self, cmd: &PlayerCommand) {
+        match cmd {
+            PlayerCommand::SetWid { wid, .. } => self.wid = Some(*wid),
+            PlayerCommand::SetVolume { volume, .. } => self.volume = Some(*volume),
+            PlayerCommand::SetAudioTrack { track_id, .. } => self.audio_track = Some(*track_id),
+            PlayerCommand::SetSubtitleTrack { track_id, .. } => self.subtitle_track = Some(*track_id),
+            PlayerCommand::AddSubtitle { subtitle, .. } => self.subtitles.push(subtitle.clone()),
+            // Tracks belong to the file they were picked in
+            PlayerCommand::Load { .. } | PlayerCommand::Stop { .. } => {
+                self.audio_track = None;
+                self.subtitle_track = None;
+                self.subtitles.clear();
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply the recorded settings to a fresh player; returns whether media was reopened.
+    fn restore(&self, player: &mut VideoPlayer, reload: bool) -> bool {
+        if let Some(wid) = self.wid {
+            respond(None, player.set_wid(wid), "restore wid");
+        }
+        if let Some(volume) = self.volume {
+            respond(None, player.set_volume(volume), "restore volume");
+        }
+        if let Some(speed) = self.speed {
+            respond(None, player.set_speed(speed), "restore speed");
+        }
+        let Some(media) = self.media.as_ref().filter(|_| reload) else {
+            return false;
+        };
+        player.restore_tracks = Some(TrackRestore {
+            audio_track: self.audio_track,
+            subtitle_track: self.subtitle_track,
+            subtitles: self.subtitles.clone(),
+        });
+        player.load(&media.url, media.video_id.clone(), media.position, None);
+        true
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn recreate(storage: &Storage) -> Option<VideoPlayer> {
+    for attempt in 1..=RESTART_ATTEMPTS {
+        thread::sleep(RESTART_DELAY);
+        match VideoPlayer::new(storage.clone()) {
+            Ok(player) => return Some(player),
+            Err(e) => eprintln!("player restart attempt {attempt} failed: {e}"),
+        }
+    }
+    None
 }
 
+/// Keep a player running for as long as the handle lives, recreating it whenever mpv
+/// shuts down or the thread panics. Commands sent meanwhile wait in the channel.
+fn supervise(mut player: VideoPlayer, app: &AppHandle, storage: &Storage, rx: &Receiver<PlayerCommand>) {
+    let mut session = Session::default();
+    let mut last_restart: Option<Instant> = None;
+    loop {
+        let reason = match panic::catch_unwind(AssertUnwindSafe(|| player.run(app, rx, &mut session))) {
+            Ok(Exit::Disconnected) => return,
+            Ok(Exit::Shutdown) => "mpv shut down".to_string(),
+            Err(panic) => format!("player thread panicked: {}", panic_message(panic)),
+        };
+        eprintln!("{reason}; restarting player");
+        player = match recreate(storage) {
+            Some(player) => player,
+            None => {
+                eprintln!("giving up on restarting the player");
+                return;
+            }
+        };
+        let crash_loop = last_restart.is_some_and(|t| t.elapsed() < CRASH_LOOP_WINDOW);
+        last_restart = Some(Instant::now());
+        let reload = !crash_loop && settings::load(storage, RELOAD_ON_RESTART_KEY).ok().flatten().unwrap_or(true);
+        let reloaded = session.restore(&mut player, reload);
+        let _ = app.emit(EVENT_RESTARTED, PlayerRestarted { reason, reloaded });
+    }
+}
+
+/// Commands for the player thread. Those without a result of their own may carry a reply
+/// channel to learn whether mpv accepted them.
 pub enum PlayerCommand {
-    Load { url: String, start_time: Option<f64> },
-    PauseToggle,
-    Stop,
-    SetWid { wid: i64 },
-    SeekRelative { seconds: f64 },
-    SeekAbsolute { seconds: f64 },
-    SetVolume { volume: f64 },
-    GetVolume { reply: Sender<Result<f64, String>> },
-    Position { reply: Sender<Result<f64, String>> },
-    Duration { reply: Sender<Result<f64, String>> },
-    CycleAudio,
-    CycleSubtitle,
-    ToggleSubtitleVisibility,
-    SetAudioTrack { track_id: i64 },
-    SetSubtitleTrack { track_id: i64 },
-    Screenshot { file: String, include_subs: bool },
+    Load { url: String, video_id: Option<String>, start_time: Option<f64>, reply: Option<Reply<()>> },
+    PauseToggle { reply: Option<Reply<()>> },
+    Stop { reply: Option<Reply<()>> },
+    SetWid { wid: i64, reply: Option<Reply<()>> },
+    SeekRelative { seconds: f64, reply: Option<Reply<()>> },
+    SeekAbsolute { seconds: f64, reply: Option<Reply<()>> },
+    SetVolume { volume: f64, reply: Option<Reply<()>> },
+    GetVolume { reply: Reply<f64> },
+    Position { reply: Reply<f64> },
+    Duration { reply: Reply<f64> },
+    State { reply: Reply<PlayerState> },
+    Tracks { reply: Reply<Vec<Track>> },
+    SetWatchedThreshold { threshold: f64, reply: Option<Reply<()>> },
+    SetSpeed { speed: f64, reply: Option<Reply<()>> },
+    CycleSpeed { backwards: bool, reply: Reply<f64> },
+    FrameStep { backwards: bool, reply: Option<Reply<()>> },
+    CycleAudio { reply: Option<Reply<()>> },
+    CycleSubtitle { reply: Option<Reply<()>> },
+    ToggleSubtitleVisibility { reply: Option<Reply<()>> },
+    SetAudioTrack { track_id: i64, reply: Option<Reply<()>> },
+    SetSubtitleTrack { track_id: i64, reply: Option<Reply<()>> },
+    SetSubtitleStyle { style: SubtitleStyle, reply: Option<Reply<()>> },
+    SetSubtitleDelay { seconds: f64, reply: Option<Reply<()>> },
+    SetAudioDelay { seconds: f64, reply: Option<Reply<()>> },
+    AddSubtitle { subtitle: ExternalSubtitle, select: bool, reply: Option<Reply<()>> },
+    Screenshot { file: String, include_subs: bool, reply: Option<Reply<()>> },
+}
+
+// The thread only goes away if mpv failed to initialise or could not be restarted
+fn disconnected<E>(_: E) -> AppError {
+    AppError::Player("player is not running".to_string())
 }
 
 #[derive(Clone)]
@@ -139,144 +872,145 @@ pub struct PlayerHandle {
 }
 
 impl PlayerHandle {
-    pub fn load(&self, url: String, start_time: Option<f64>) -> Result<(), String> {
+    /// Send a command and wait for the player thread to answer it.
+    fn request<T>(&self, command: impl FnOnce(Reply<T>) -> PlayerCommand) -> Result<T, AppError> {
+        let (tx, rx) = mpsc::channel();
+        self.tx.send(command(tx)).map_err(disconnected)?;
+        // The reply is dropped unanswered when the player dies mid-command
+        rx.recv()
+            .map_err(|_| AppError::Player("player stopped while handling the command".to_string()))?
+    }
+
+    /// Blocks until mpv has opened the file or failed to, for at most `LOAD_TIMEOUT`.
+    pub fn load(&self, url: String, video_id: Option<String>, start_time: Option<f64>) -> Result<(), AppError> {
+        let (tx, rx) = mpsc::channel();
         self.tx
-            .send(PlayerCommand::Load { url, start_time })
-            .map_err(|e| e.to_string())
+            .send(PlayerCommand::Load { url, video_id, start_time, reply: Some(tx) })
+            .map_err(disconnected)?;
+        match rx.recv_timeout(LOAD_TIMEOUT) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(AppError::Player("timed out opening the file".to_string())),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(AppError::Player("player stopped while handling the command".to_string()))
+            }
+        }
     }
 
-    pub fn pause_toggle(&self) -> Result<(), String> {
-        self.tx.send(PlayerCommand::PauseToggle).map_err(|e| e.to_string())
+    pub fn pause_toggle(&self) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::PauseToggle { reply: Some(reply) })
     }
 
-    pub fn stop(&self) -> Result<(), String> {
-        self.tx.send(PlayerCommand::Stop).map_err(|e| e.to_string())
+    pub fn stop(&self) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::Stop { reply: Some(reply) })
     }
 
-    pub fn set_wid(&self, wid: i64) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::SetWid { wid })
-            .map_err(|e| e.to_string())
+    pub fn set_wid(&self, wid: i64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetWid { wid, reply: Some(reply) })
     }
 
-    pub fn seek_relative(&self, seconds: f64) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::SeekRelative { seconds })
-            .map_err(|e| e.to_string())
+    pub fn seek_relative(&self, seconds: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SeekRelative { seconds, reply: Some(reply) })
     }
 
-    pub fn seek_absolute(&self, seconds: f64) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::SeekAbsolute { seconds })
-            .map_err(|e| e.to_string())
+    pub fn seek_absolute(&self, seconds: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SeekAbsolute { seconds, reply: Some(reply) })
     }
 
-    pub fn set_volume(&self, volume: f64) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::SetVolume { volume })
-            .map_err(|e| e.to_string())
+    pub fn set_volume(&self, volume: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetVolume { volume, reply: Some(reply) })
     }
 
-    pub fn get_volume(&self) -> Result<f64, String> {
-        let (tx, rx) = mpsc::channel();
-        self.tx
-            .send(PlayerCommand::GetVolume { reply: tx })
-            .map_err(|e| e.to_string())?;
-        rx.recv().map_err(|e| e.to_string())?
+    pub fn get_volume(&self) -> Result<f64, AppError> {
+        self.request(|reply| PlayerCommand::GetVolume { reply })
     }
 
-    pub fn position(&self) -> Result<f64, String> {
-        let (tx, rx) = mpsc::channel();
-        self.tx
-            .send(PlayerCommand::Position { reply: tx })
-            .map_err(|e| e.to_string())?;
-        rx.recv().map_err(|e| e.to_string())?
+    pub fn position(&self) -> Result<f64, AppError> {
+        self.request(|reply| PlayerCommand::Position { reply })
     }
 
-    pub fn duration(&self) -> Result<f64, String> {
-        let (tx, rx) = mpsc::channel();
-        self.tx
-            .send(PlayerCommand::Duration { reply: tx })
-            .map_err(|e| e.to_string())?;
-        rx.recv().map_err(|e| e.to_string())?
+    pub fn duration(&self) -> Result<f64, AppError> {
+        self.request(|reply| PlayerCommand::Duration { reply })
     }
 
-    pub fn cycle_audio(&self) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::CycleAudio)
-            .map_err(|e| e.to_string())
+    pub fn state(&self) -> Result<PlayerState, AppError> {
+        self.request(|reply| PlayerCommand::State { reply })
     }
 
-    pub fn cycle_subtitle(&self) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::CycleSubtitle)
-            .map_err(|e| e.to_string())
+    pub fn tracks(&self) -> Result<Vec<Track>, AppError> {
+        self.request(|reply| PlayerCommand::Tracks { reply })
     }
 
-    pub fn toggle_subtitle_visibility(&self) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::ToggleSubtitleVisibility)
-            .map_err(|e| e.to_string())
+    pub fn set_watched_threshold(&self, threshold: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetWatchedThreshold { threshold, reply: Some(reply) })
     }
 
-    pub fn set_audio_track(&self, track_id: i64) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::SetAudioTrack { track_id })
-            .map_err(|e| e.to_string())
+    /// Clamped to 0.25–4x.
+    pub fn set_speed(&self, speed: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetSpeed { speed, reply: Some(reply) })
     }
 
-    pub fn set_subtitle_track(&self, track_id: i64) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::SetSubtitleTrack { track_id })
-            .map_err(|e| e.to_string())
+    /// Returns the new speed.
+    pub fn cycle_speed(&self, backwards: bool) -> Result<f64, AppError> {
+        self.request(|reply| PlayerCommand::CycleSpeed { backwards, reply })
     }
 
-    pub fn screenshot_to_file(&self, file: String, include_subs: bool) -> Result<(), String> {
-        self.tx
-            .send(PlayerCommand::Screenshot { file, include_subs })
-            .map_err(|e| e.to_string())
+    pub fn frame_step(&self, backwards: bool) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::FrameStep { backwards, reply: Some(reply) })
+    }
+
+    pub fn cycle_audio(&self) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::CycleAudio { reply: Some(reply) })
+    }
+
+    pub fn cycle_subtitle(&self) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::CycleSubtitle { reply: Some(reply) })
+    }
+
+    pub fn toggle_subtitle_visibility(&self) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::ToggleSubtitleVisibility { reply: Some(reply) })
+    }
+
+    pub fn set_audio_track(&self, track_id: i64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetAudioTrack { track_id, reply: Some(reply) })
+    }
+
+    pub fn set_subtitle_track(&self, track_id: i64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetSubtitleTrack { track_id, reply: Some(reply) })
+    }
+
+    pub fn set_subtitle_style(&self, style: SubtitleStyle) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetSubtitleStyle { style, reply: Some(reply) })
+    }
+
+    pub fn set_subtitle_delay(&self, seconds: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetSubtitleDelay { seconds, reply: Some(reply) })
+    }
+
+    pub fn set_audio_delay(&self, seconds: f64) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::SetAudioDelay { seconds, reply: Some(reply) })
+    }
+
+    /// Attach a subtitle file path or URL to the current file and select it.
+    pub fn add_subtitle(&self, url: String, title: Option<String>, lang: Option<String>) -> Result<(), AppError> {
+        let subtitle = ExternalSubtitle { url, title, lang };
+        self.request(|reply| PlayerCommand::AddSubtitle { subtitle, select: true, reply: Some(reply) })
+    }
+
+    pub fn screenshot_to_file(&self, file: String, include_subs: bool) -> Result<(), AppError> {
+        self.request(|reply| PlayerCommand::Screenshot { file, include_subs, reply: Some(reply) })
     }
 }
 
-pub fn spawn_player_service() -> Result<PlayerHandle, Box<dyn Error>> {
+pub fn spawn_player_service(app: AppHandle, storage: Storage) -> Result<PlayerHandle, Box<dyn Error>> {
     let (tx, rx): (Sender<PlayerCommand>, Receiver<PlayerCommand>) = mpsc::channel();
     // Notify the spawner whether initialization succeeded
     let (ready_tx, ready_rx) = mpsc::channel();
     thread::spawn(move || {
-        let inner = VideoPlayer::new();
+        let inner = VideoPlayer::new(storage.clone());
         match inner {
             Ok(player) => {
                 let _ = ready_tx.send(Ok::<(), String>(())) ;
-                while let Ok(cmd) = rx.recv() {
-                    match cmd {
-                        PlayerCommand::Load { url, start_time } => {
-                            if let Err(e) = player.load(&url, start_time) { eprintln!("load error: {e}"); }
-                        }
-                        PlayerCommand::SetWid { wid } => {
-                            if let Err(e) = player.set_wid(wid) { eprintln!("set wid error: {e}"); }
-                        }
-                        PlayerCommand::PauseToggle => { if let Err(e) = player.pause_toggle() { eprintln!("pause error: {e}"); } }
-                        PlayerCommand::Stop => { if let Err(e) = player.stop() { eprintln!("stop error: {e}"); } }
-                        PlayerCommand::SeekRelative { seconds } => { if let Err(e) = player.seek_relative(seconds) { eprintln!("seek rel error: {e}"); } }
-                        PlayerCommand::SeekAbsolute { seconds } => { if let Err(e) = player.seek_absolute(seconds) { eprintln!("seek abs error: {e}"); } }
-                        PlayerCommand::SetVolume { volume } => { if let Err(e) = player.set_volume(volume) { eprintln!("volume error: {e}"); } }
-                        PlayerCommand::GetVolume { reply } => {
-                            let _ = reply.send(player.get_volume().map_err(|e| e.to_string()));
-                        }
-                        PlayerCommand::Position { reply } => {
-                            let _ = reply.send(player.position().map_err(|e| e.to_string()));
-                        }
-                        PlayerCommand::Duration { reply } => {
-                            let _ = reply.send(player.duration().map_err(|e| e.to_string()));
-                        }
-                        PlayerCommand::CycleAudio => { if let Err(e) = player.cycle_audio() { eprintln!("cycle audio error: {e}"); } }
-                        PlayerCommand::CycleSubtitle => { if let Err(e) = player.cycle_subtitle() { eprintln!("cycle sub error: {e}"); } }
-                        PlayerCommand::ToggleSubtitleVisibility => { if let Err(e) = player.toggle_subtitle_visibility() { eprintln!("toggle sub vis error: {e}"); } }
-                        PlayerCommand::SetAudioTrack { track_id } => { if let Err(e) = player.set_audio_track(track_id) { eprintln!("set aid error: {e}"); } }
-                        PlayerCommand::SetSubtitleTrack { track_id } => { if let Err(e) = player.set_subtitle_track(track_id) { eprintln!("set sid error: {e}"); } }
-                        PlayerCommand::Screenshot { file, include_subs } => { if let Err(e) = player.screenshot_to_file(&file, include_subs) { eprintln!("screenshot error: {e}"); } }
-                    }
-                }
+                supervise(player, &app, &storage, &rx);
             }
             Err(e) => {
                 let _ = ready_tx.send(Err::<(), String>(e.to_string()));
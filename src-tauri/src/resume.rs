@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+
+use crate::player::PlayerState;
+use crate::settings;
+use crate::storage::{PlaybackSession, Storage, WatchEventKind};
+
+// How often the resume position of the current item is written to the session store
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(15);
+// Fraction of the duration after which an item counts as watched
+const DEFAULT_WATCHED_THRESHOLD: f64 = 0.9;
+
+pub const WATCHED_THRESHOLD_KEY: &str = "watched_threshold";
+
+/// The item whose progress is being tracked for resume.
+struct NowPlaying {
+    video_id: String,
+    start_time: f64,
+    watched: bool,
+    last_checkpoint: Instant,
+}
+
+/// Owned by the player thread; persists the position of whatever is loaded.
+pub struct ResumeTracker {
+    storage: Storage,
+    now_playing: Option<NowPlaying>,
+    // Set by `queue` and promoted to `now_playing` once mpv starts the new file,
+    // so the end-file event of the previous file is not attributed to it
+    queued: Option<NowPlaying>,
+    watched_threshold: f64,
+}
+
+impl ResumeTracker {
+    pub fn new(storage: Storage) -> Self {
+        let watched_threshold = settings::load(&storage, WATCHED_THRESHOLD_KEY)
+            .ok()
+            .flatten()
+            .unwrap_or(DEFAULT_WATCHED_THRESHOLD);
+        Self {
+            storage,
+            now_playing: None,
+            queued: None,
+            watched_threshold,
+        }
+    }
+
+    pub fn set_watched_threshold(&mut self, threshold: f64) {
+        self.watched_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    /// Called before a new file is loaded.
+    pub fn queue(&mut self, video_id: Option<String>, start_time: Option<f64>, state: &PlayerState) {
+        self.checkpoint(state);
+        self.now_playing = None;
+        self.queued = video_id.map(|video_id| {
+            // Rewatching something finished must not mark it unwatched again
+            let watched = match self.storage.get_playback_session(&video_id) {
+                Ok(session) => session.is_some_and(|s| s.watched),
+                Err(e) => {
+                    eprintln!("session lookup error: {e}");
+                    false
+                }
+            };
+            NowPlaying {
+                video_id,
+                start_time: start_time.unwrap_or(0.0),
+                watched,
+                last_checkpoint: Instant::now(),
+            }
+        });
+    }
+
+    pub fn start_file(&mut self) {
+        self.now_playing = self.queued.take();
+        if let Some(item) = &self.now_playing {
+            self.record(&item.video_id, WatchEventKind::Started, item.start_time);
+        }
+    }
+
+    pub fn pause_changed(&self, paused: bool, state: &PlayerState) {
+        let (Some(item), Some(position)) = (&self.now_playing, state.time_pos) else {
+            return;
+        };
+        let kind = if paused { WatchEventKind::Paused } else { WatchEventKind::Resumed };
+        self.record(&item.video_id, kind, position);
+    }
+
+    fn record(&self, video_id: &str, kind: WatchEventKind, position: f64) {
+        if let Err(e) = self.storage.record_watch_event(video_id, kind, position.max(0.0) as u64) {
+            eprintln!("watch event error: {e}");
+        }
+    }
+
+    pub fn end_file(&mut self, state: &PlayerState) {
+        self.checkpoint(state);
+        self.now_playing = None;
+    }
+
+    pub fn stop(&mut self, state: &PlayerState) {
+        self.end_file(state);
+        self.queued = None;
+    }
+
+    /// Called on every time-pos change; only writes every `CHECKPOINT_INTERVAL`.
+    pub fn tick(&mut self, state: &PlayerState) {
+        let due = self
+            .now_playing
+            .as_ref()
+            .is_some_and(|p| p.last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL);
+        if due {
+            self.checkpoint(state);
+        }
+    }
+
+    /// Persist the current position of the tracked item, marking it watched past the threshold.
+    pub fn checkpoint(&mut self, state: &PlayerState) {
+        let (Some(item), Some(position)) = (self.now_playing.as_mut(), state.time_pos) else {
+            return;
+        };
+        item.last_checkpoint = Instant::now();
+        if let Some(duration) = state.duration.filter(|d| *d > 0.0) {
+            item.watched |= position >= duration * self.watched_threshold;
+        }
+        let session = PlaybackSession {
+            video_id: item.video_id.clone(),
+            timestamp: position.max(0.0) as u64,
+            watched: item.watched,
+            duration: state.duration.map(|d| d as u64),
+            season: None,
+            episode: None,
+            updated_at: 0,
+        };
+        if let Err(e) = self.storage.save_playback_session(&session) {
+            eprintln!("session checkpoint error: {e}");
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::addon::{self, AddonClient};
+use crate::error::AppError;
+use crate::storage::PlaybackSession;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EpisodeProgress {
+    pub video_id: String,
+    pub season: u32,
+    pub episode: u32,
+    pub name: Option<String>,
+    pub released: Option<String>,
+    pub watched: bool,
+    pub timestamp: u64,
+    pub duration: Option<u64>,
+}
+
+/// Fetch the episode list of a series from Cinemeta, in season/episode order.
+pub async fn fetch_episodes(series_id: &str) -> Result<Vec<EpisodeProgress>, AppError> {
+    let cinemeta = AddonClient::new(addon::http_client()?, addon::CINEMETA_URL);
+    let meta = cinemeta.meta("series", series_id).await?;
+
+    let mut episodes: Vec<EpisodeProgress> = meta
+        .meta
+        .videos
+        .into_iter()
+        .filter_map(|v| {
+            Some(EpisodeProgress {
+                season: v.season?,
+                episode: v.episode.or(v.number)?,
+                video_id: v.id,
+                name: v.name.or(v.title),
+                released: v.released,
+                watched: false,
+                timestamp: 0,
+                duration: None,
+            })
+        })
+        .collect();
+    episodes.sort_by_key(|e| (e.season, e.episode));
+    Ok(episodes)
+}
+
+/// Merge stored sessions into the episode list.
+pub fn apply_sessions(episodes: &mut [EpisodeProgress], sessions: &[PlaybackSession]) {
+    let by_id: HashMap<&str, &PlaybackSession> = sessions.iter().map(|s| (s.video_id.as_str(), s)).collect();
+    for episode in episodes.iter_mut() {
+        if let Some(session) = by_id.get(episode.video_id.as_str()) {
+            episode.watched = session.watched;
+            episode.timestamp = session.timestamp;
+            episode.duration = session.duration;
+        }
+    }
+}
+
+/// The episode to offer next: the one last played if it was left unfinished, otherwise the
+/// first unwatched regular episode after it. Season 0 (specials) is never suggested.
+pub fn next_up(episodes: &[EpisodeProgress], sessions: &[PlaybackSession]) -> Option<EpisodeProgress> {
+    let regular = || episodes.iter().filter(|e| e.season > 0);
+    let Some(latest) = sessions.iter().max_by_key(|s| s.updated_at) else {
+        return regular().next().cloned();
+    };
+    let position = regular().position(|e| e.video_id == latest.video_id);
+    if !latest.watched {
+        if let Some(i) = position {
+            return regular().nth(i).cloned();
+        }
+    }
+    regular()
+        .skip(position.map_or(0, |i| i + 1))
+        .find(|e| !e.watched)
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES: &str = "tt0903747";
+
+    fn episode(season: u32, episode: u32) -> EpisodeProgress {
+        EpisodeProgress {
+            video_id: format!("{SERIES}:{season}:{episode}"),
+            season,
+            episode,
+            name: None,
+            released: None,
+            watched: false,
+            timestamp: 0,
+            duration: None,
+        }
+    }
+
+    fn session(season: u32, episode: u32, watched: bool, updated_at: u64) -> PlaybackSession {
+        PlaybackSession {
+            video_id: format!("{SERIES}:{season}:{episode}"),
+            timestamp: if watched { 2700 } else { 600 },
+            watched,
+            duration: Some(2800),
+            season: Some(season),
+            episode: Some(episode),
+            updated_at,
+        }
+    }
+
+    fn episodes(sessions: &[PlaybackSession]) -> Vec<EpisodeProgress> {
+        let mut episodes = vec![episode(0, 1), episode(1, 1), episode(1, 2), episode(1, 3), episode(2, 1)];
+        apply_sessions(&mut episodes, sessions);
+        episodes
+    }
+
+    fn next(sessions: &[PlaybackSession]) -> Option<(u32, u32)> {
+        next_up(&episodes(sessions), sessions).map(|e| (e.season, e.episode))
+    }
+
+    #[test]
+    fn starts_with_first_regular_episode() {
+        assert_eq!(next(&[]), Some((1, 1)));
+    }
+
+    #[test]
+    fn resumes_unfinished_episode() {
+        assert_eq!(next(&[session(1, 1, true, 10), session(1, 2, false, 20)]), Some((1, 2)));
+    }
+
+    #[test]
+    fn continues_after_latest_watched_episode() {
+        // Going by the latest session, not the furthest one
+        let sessions = [session(1, 3, true, 10), session(1, 1, true, 20)];
+        assert_eq!(next(&sessions), Some((1, 2)));
+    }
+
+    #[test]
+    fn skips_watched_episodes_across_seasons() {
+        let sessions = [session(1, 3, true, 10), session(1, 2, true, 20)];
+        assert_eq!(next(&sessions), Some((2, 1)));
+    }
+
+    #[test]
+    fn special_does_not_move_progress() {
+        let sessions = [session(1, 1, true, 10), session(0, 1, true, 20)];
+        assert_eq!(next(&sessions), Some((1, 2)));
+    }
+
+    #[test]
+    fn nothing_after_finale() {
+        let sessions = [session(1, 3, true, 10), session(2, 1, true, 20)];
+        assert_eq!(next(&sessions), None);
+    }
+}
@@ -0,0 +1,78 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::addon::{self, AddonClient, Manifest};
+use crate::error::AppError;
+use crate::storage::Storage;
+
+const STREAM_PROVIDER_KEY: &str = "stream_provider";
+
+/// Read a typed setting; `None` when it was never set.
+pub fn load<T: DeserializeOwned>(storage: &Storage, key: &str) -> Result<Option<T>, AppError> {
+    storage
+        .get_setting(key)?
+        .map(|value| serde_json::from_value(value).map_err(|e| AppError::Parse(format!("invalid setting {key}: {e}"))))
+        .transpose()
+}
+
+pub fn save<T: Serialize>(storage: &Storage, key: &str, value: &T) -> Result<(), AppError> {
+    let value = serde_json::to_value(value)?;
+    storage.set_setting(key, &value)
+}
+
+/// An AIOStreams instance and the credentials generated by its `/stremio/configure` flow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamProvider {
+    pub base_url: String,
+    pub uuid: Option<String>,
+    pub encrypted_password: Option<String>,
+}
+
+impl StreamProvider {
+    /// Accepts the instance root with or without a trailing `/stremio`.
+    pub fn new(base_url: &str, uuid: Option<String>, encrypted_password: Option<String>) -> Result<Self, AppError> {
+        let trimmed = base_url.trim().trim_end_matches('/');
+        let trimmed = trimmed.strip_suffix("/stremio").unwrap_or(trimmed);
+        let parsed = reqwest::Url::parse(trimmed)
+            .map_err(|e| AppError::Config(format!("invalid stream provider URL: {e}")))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(AppError::Config(format!(
+                "invalid stream provider URL: unsupported scheme {}",
+                parsed.scheme()
+            )));
+        }
+        let non_empty = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        Ok(Self {
+            base_url: trimmed.to_string(),
+            uuid: non_empty(uuid),
+            encrypted_password: non_empty(encrypted_password),
+        })
+    }
+
+    /// Base of the authenticated Stremio add-on routes.
+    pub fn addon_url(&self) -> Result<String, AppError> {
+        match (&self.uuid, &self.encrypted_password) {
+            (Some(uuid), Some(password)) => Ok(format!("{}/stremio/{}/{}", self.base_url, uuid, password)),
+            _ => Err(AppError::Config("stream provider credentials are missing".to_string())),
+        }
+    }
+
+    /// Fetch the user-specific manifest to check that the URL and credentials work.
+    pub async fn validate(&self) -> Result<Manifest, AppError> {
+        let http = addon::http_client()?;
+        let manifest = AddonClient::new(http, &self.addon_url()?).manifest().await?;
+        if !manifest.resources.iter().any(|r| r.name() == "stream") {
+            return Err(AppError::Config("stream provider add-on does not provide streams".to_string()));
+        }
+        Ok(manifest)
+    }
+}
+
+/// `None` when no stream provider was configured yet.
+pub fn stream_provider(storage: &Storage) -> Result<Option<StreamProvider>, AppError> {
+    load(storage, STREAM_PROVIDER_KEY)
+}
+
+pub fn set_stream_provider(storage: &Storage, provider: &StreamProvider) -> Result<(), AppError> {
+    save(storage, STREAM_PROVIDER_KEY, provider)
+}
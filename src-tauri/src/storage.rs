@@ -0,0 +1,985 @@
+use directories::ProjectDirs;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::addon::{InstalledAddon, Manifest};
+use crate::error::AppError;
+
+// Number of rolling database backups kept under `backups/`
+const BACKUP_COUNT: usize = 3;
+// A new backup is taken at startup when the newest one is older than this
+const BACKUP_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+// Each entry upgrades the schema by one version; never edit an entry once released.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE sessions (
+        imdb_id TEXT PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        watched INTEGER NOT NULL DEFAULT 0,
+        duration INTEGER,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        imdb_id TEXT NOT NULL,
+        watched_at INTEGER NOT NULL
+    );
+    CREATE INDEX history_watched_at ON history (watched_at);
+    CREATE TABLE library (
+        imdb_id TEXT PRIMARY KEY,
+        type TEXT NOT NULL,
+        name TEXT NOT NULL,
+        poster TEXT,
+        added_at INTEGER NOT NULL
+    );
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // 2: sessions and history keyed by the full Stremio video id (`tt1234567:1:2`)
+    "ALTER TABLE sessions RENAME COLUMN imdb_id TO video_id;
+    ALTER TABLE sessions ADD COLUMN series_id TEXT;
+    ALTER TABLE sessions ADD COLUMN season INTEGER;
+    ALTER TABLE sessions ADD COLUMN episode INTEGER;
+    UPDATE sessions SET series_id = CASE
+        WHEN instr(video_id, ':') > 0 THEN substr(video_id, 1, instr(video_id, ':') - 1)
+        ELSE video_id
+    END;
+    CREATE INDEX sessions_series ON sessions (series_id, season, episode);
+    ALTER TABLE history RENAME COLUMN imdb_id TO video_id;",
+    // 3: append-only watch event timeline replaces the finished-only history
+    "CREATE TABLE watch_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        video_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        position INTEGER NOT NULL DEFAULT 0,
+        occurred_at INTEGER NOT NULL
+    );
+    CREATE INDEX watch_events_occurred_at ON watch_events (occurred_at);
+    INSERT INTO watch_events (video_id, kind, position, occurred_at)
+        SELECT h.video_id, 'finished', COALESCE(s.timestamp, 0), h.watched_at
+        FROM history h LEFT JOIN sessions s ON s.video_id = h.video_id
+        ORDER BY h.id;
+    DROP TABLE history;
+    CREATE INDEX sessions_updated_at ON sessions (updated_at);",
+    // 4: installed add-ons with their manifests, in user-defined order
+    "CREATE TABLE addons (
+        transport_url TEXT PRIMARY KEY,
+        manifest TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        enabled INTEGER NOT NULL DEFAULT 1,
+        installed_at INTEGER NOT NULL
+    );",
+    // 5: audio/subtitle languages picked per series
+    "CREATE TABLE series_languages (
+        series_id TEXT PRIMARY KEY,
+        audio TEXT,
+        subtitle TEXT,
+        subtitles_off INTEGER NOT NULL DEFAULT 0,
+        updated_at INTEGER NOT NULL
+    );",
+    // 6: sessions hidden from continue watching keep their progress
+    "ALTER TABLE sessions ADD COLUMN dismissed INTEGER NOT NULL DEFAULT 0;",
+    // 7: season/episode for episode sessions migration 2 only gave a series_id
+    "UPDATE sessions SET
+        season = CAST(substr(rest, 1, instr(rest, ':') - 1) AS INTEGER),
+        episode = CAST(substr(rest, instr(rest, ':') + 1) AS INTEGER)
+    FROM (SELECT video_id AS id, substr(video_id, instr(video_id, ':') + 1) AS rest FROM sessions)
+    WHERE id = video_id AND season IS NULL
+        AND rest GLOB '[0-9]*:[0-9]*' AND NOT rest GLOB '*[^0-9:]*' AND NOT rest GLOB '*:*:*';",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaybackSession {
+    /// Stremio video id: `tt1234567` for movies, `tt1234567:1:2` for episodes.
+    #[serde(alias = "imdb_id")]
+    pub video_id: String,
+    pub timestamp: u64,
+    pub watched: bool,
+    #[serde(default)]
+    pub duration: Option<u64>,
+    // Derived from `video_id` when saved
+    #[serde(default)]
+    pub season: Option<u32>,
+    #[serde(default)]
+    pub episode: Option<u32>,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+/// Languages last picked while watching a series, overriding the global preferences.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SeriesLanguages {
+    pub audio: Option<String>,
+    pub subtitle: Option<String>,
+    pub subtitles_off: bool,
+}
+
+/// Split a Stremio video id into its meta id and, for episodes, season and episode numbers.
+pub fn parse_video_id(video_id: &str) -> (&str, Option<(u32, u32)>) {
+    let mut parts = video_id.splitn(3, ':');
+    let meta_id = parts.next().unwrap_or(video_id);
+    let episode = match (parts.next(), parts.next()) {
+        (Some(season), Some(episode)) => season.parse().ok().zip(episode.parse().ok()),
+        _ => None,
+    };
+    (meta_id, episode)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchEventKind {
+    Started,
+    Paused,
+    Resumed,
+    Finished,
+}
+
+impl WatchEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            WatchEventKind::Started => "started",
+            WatchEventKind::Paused => "paused",
+            WatchEventKind::Resumed => "resumed",
+            WatchEventKind::Finished => "finished",
+        }
+    }
+}
+
+impl ToSql for WatchEventKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for WatchEventKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "started" => Ok(WatchEventKind::Started),
+            "paused" => Ok(WatchEventKind::Paused),
+            "resumed" => Ok(WatchEventKind::Resumed),
+            "finished" => Ok(WatchEventKind::Finished),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// One entry of the watch history timeline; `position` is in seconds.
+#[derive(Debug, Serialize, Clone)]
+pub struct WatchEvent {
+    pub id: i64,
+    pub video_id: String,
+    pub kind: WatchEventKind,
+    pub position: u64,
+    pub occurred_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryItem {
+    pub imdb_id: String,
+    pub r#type: String,
+    pub name: String,
+    pub poster: Option<String>,
+    #[serde(default)]
+    pub added_at: u64,
+}
+
+// Format of the playback_sessions.json file used before the database existed
+#[derive(Debug, Deserialize, Default)]
+struct LegacyPlaybackSessions {
+    sessions: HashMap<String, PlaybackSession>,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn data_dir() -> Result<PathBuf, AppError> {
+    let proj_dirs = ProjectDirs::from("com", "unstrem.io", "unstrem.io")
+        .ok_or_else(|| AppError::Storage("Could not resolve project directories".to_string()))?;
+    let unstrem_dir = proj_dirs.data_dir().join("unstrem.io");
+    std::fs::create_dir_all(&unstrem_dir)?;
+    Ok(unstrem_dir)
+}
+
+/// A directory under the data dir for one of the disk caches, created if missing.
+pub fn cache_dir(name: &str) -> Result<PathBuf, AppError> {
+    let dir = data_dir()?.join(name);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// File name for a cache key such as a URL. FNV-1a, so names stay stable across Rust versions.
+pub fn cache_key(key: &str) -> String {
+    let hash = key
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("{hash:016x}")
+}
+
+/// Total size of the files in a cache directory, in bytes.
+pub fn dir_size(dir: &Path) -> Result<u64, AppError> {
+    Ok(std::fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum())
+}
+
+pub fn clear_dir(dir: &Path) -> Result<(), AppError> {
+    std::fs::remove_dir_all(dir)?;
+    Ok(std::fs::create_dir_all(dir)?)
+}
+
+/// Handle to the local SQLite database, shared by IPC commands and the player thread.
+#[derive(Clone)]
+pub struct Storage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Storage {
+    pub fn open() -> Result<Self, AppError> {
+        let dir = data_dir()?;
+        let conn = open_or_restore(&dir.join("unstrem.db"), &dir.join("backups"))?;
+        import_legacy_sessions(&conn, &dir.join("playback_sessions.json"))?;
+        if let Err(e) = rotate_backups(&conn, &dir.join("backups")) {
+            eprintln!("Database backup failed: {e}");
+        }
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        // A panic while holding the lock cannot leave SQLite itself inconsistent
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn save_playback_session(&self, session: &PlaybackSession) -> Result<(), AppError> {
+        let (series_id, episode) = parse_video_id(&session.video_id);
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let was_watched: bool = tx
+            .query_row("SELECT watched FROM sessions WHERE video_id = ?1", [&session.video_id], |row| row.get(0))
+            .optional()?
+            .unwrap_or(false);
+        let now = unix_now();
+        tx.execute(
+            "INSERT INTO sessions (video_id, series_id, season, episode, timestamp, watched, duration, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (video_id) DO UPDATE SET
+                timestamp = excluded.timestamp,
+                watched = excluded.watched,
+                duration = excluded.duration,
+                updated_at = excluded.updated_at,
+                dismissed = 0",
+            params![
+                session.video_id,
+                series_id,
+                episode.map(|(s, _)| s),
+                episode.map(|(_, e)| e),
+                session.timestamp,
+                session.watched,
+                session.duration,
+                now
+            ],
+        )?;
+        if session.watched && !was_watched {
+            tx.execute(
+                "INSERT INTO watch_events (video_id, kind, position, occurred_at) VALUES (?1, ?2, ?3, ?4)",
+                params![session.video_id, WatchEventKind::Finished, session.timestamp, now],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    pub fn get_playback_session(&self, video_id: &str) -> Result<Option<PlaybackSession>, AppError> {
+        self.conn()
+            .query_row(
+                &format!("SELECT {SESSION_COLUMNS} FROM sessions WHERE video_id = ?1"),
+                [video_id],
+                session_from_row,
+            )
+            .optional()
+            .map_err(AppError::from)
+    }
+
+    /// All stored sessions for episodes of a series, in season/episode order.
+    pub fn series_sessions(&self, series_id: &str) -> Result<Vec<PlaybackSession>, AppError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS} FROM sessions
+                 WHERE series_id = ?1 AND season IS NOT NULL
+                 ORDER BY season, episode"
+        ))?;
+        let rows = stmt.query_map([series_id], session_from_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+    }
+
+    /// Sessions left unfinished, most recently played first, with one row per series.
+    /// A series stays hidden once its latest session was dismissed, until it is played again.
+    pub fn continue_watching(&self, limit: u32) -> Result<Vec<PlaybackSession>, AppError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS} FROM sessions s
+                 WHERE watched = 0 AND timestamp > 0 AND dismissed = 0
+                   AND updated_at = (SELECT MAX(updated_at) FROM sessions WHERE series_id = s.series_id)
+                 ORDER BY updated_at DESC LIMIT ?1"
+        ))?;
+        let rows = stmt.query_map([limit], session_from_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+    }
+
+    /// Hide a session from continue watching; its resume position and watched flag are kept.
+    pub fn dismiss_playback_session(&self, video_id: &str) -> Result<(), AppError> {
+        self.conn()
+            .execute("UPDATE sessions SET dismissed = 1 WHERE video_id = ?1", [video_id])
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn record_watch_event(&self, video_id: &str, kind: WatchEventKind, position: u64) -> Result<(), AppError> {
+        self.conn()
+            .execute(
+                "INSERT INTO watch_events (video_id, kind, position, occurred_at) VALUES (?1, ?2, ?3, ?4)",
+                params![video_id, kind, position, unix_now()],
+            )
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    /// Most recent watch events first; pass the smallest id seen as `before` to page further back.
+    pub fn watch_history(&self, limit: u32, before: Option<i64>) -> Result<Vec<WatchEvent>, AppError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, video_id, kind, position, occurred_at FROM watch_events
+                 WHERE ?1 IS NULL OR id < ?1
+                 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![before, limit], |row| {
+            Ok(WatchEvent {
+                id: row.get(0)?,
+                video_id: row.get(1)?,
+                kind: row.get(2)?,
+                position: row.get(3)?,
+                occurred_at: row.get(4)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+    }
+
+    pub fn delete_watch_event(&self, id: i64) -> Result<(), AppError> {
+        self.conn()
+            .execute("DELETE FROM watch_events WHERE id = ?1", [id])
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn clear_watch_history(&self) -> Result<(), AppError> {
+        self.conn()
+            .execute("DELETE FROM watch_events", [])
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn library_add(&self, item: &LibraryItem) -> Result<(), AppError> {
+        self.conn()
+            .execute(
+                "INSERT INTO library (imdb_id, type, name, poster, added_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (imdb_id) DO UPDATE SET
+                    type = excluded.type,
+                    name = excluded.name,
+                    poster = excluded.poster",
+                params![item.imdb_id, item.r#type, item.name, item.poster, unix_now()],
+            )
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn library_remove(&self, imdb_id: &str) -> Result<(), AppError> {
+        self.conn()
+            .execute("DELETE FROM library WHERE imdb_id = ?1", [imdb_id])
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn library(&self) -> Result<Vec<LibraryItem>, AppError> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT imdb_id, type, name, poster, added_at FROM library ORDER BY added_at DESC")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(LibraryItem {
+                imdb_id: row.get(0)?,
+                r#type: row.get(1)?,
+                name: row.get(2)?,
+                poster: row.get(3)?,
+                added_at: row.get(4)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+    }
+
+    pub fn series_languages(&self, series_id: &str) -> Result<Option<SeriesLanguages>, AppError> {
+        self.conn()
+            .query_row(
+                "SELECT audio, subtitle, subtitles_off FROM series_languages WHERE series_id = ?1",
+                [series_id],
+                |row| {
+                    Ok(SeriesLanguages {
+                        audio: row.get(0)?,
+                        subtitle: row.get(1)?,
+                        subtitles_off: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(AppError::from)
+    }
+
+    pub fn set_series_languages(&self, series_id: &str, languages: &SeriesLanguages) -> Result<(), AppError> {
+        self.conn()
+            .execute(
+                "INSERT INTO series_languages (series_id, audio, subtitle, subtitles_off, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (series_id) DO UPDATE SET
+                    audio = excluded.audio,
+                    subtitle = excluded.subtitle,
+                    subtitles_off = excluded.subtitles_off,
+                    updated_at = excluded.updated_at",
+                params![series_id, languages.audio, languages.subtitle, languages.subtitles_off, unix_now()],
+            )
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn clear_series_languages(&self, series_id: &str) -> Result<(), AppError> {
+        self.conn()
+            .execute("DELETE FROM series_languages WHERE series_id = ?1", [series_id])
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    /// Settings are stored as JSON values so callers can keep typed structs on top.
+    pub fn get_setting(&self, key: &str) -> Result<Option<Value>, AppError> {
+        let raw: Option<String> = self
+            .conn()
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?;
+        raw.map(|s| serde_json::from_str(&s).map_err(AppError::from)).transpose()
+    }
+
+    pub fn installed_addons(&self) -> Result<Vec<InstalledAddon>, AppError> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT transport_url, manifest, position, enabled FROM addons ORDER BY position")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?;
+        let mut addons = Vec::new();
+        for row in rows {
+            let (transport_url, manifest, position, enabled) = row?;
+            match serde_json::from_str(&manifest) {
+                Ok(manifest) => addons.push(InstalledAddon {
+                    transport_url,
+                    manifest,
+                    position,
+                    enabled,
+                }),
+                Err(e) => eprintln!("Skipping add-on {} with unreadable manifest: {}", transport_url, e),
+            }
+        }
+        Ok(addons)
+    }
+
+    /// Appends a new add-on at the end; reinstalling refreshes the manifest but keeps order and state.
+    pub fn install_addon(&self, transport_url: &str, manifest: &Manifest) -> Result<(), AppError> {
+        let raw = serde_json::to_string(manifest)?;
+        self.conn()
+            .execute(
+                "INSERT INTO addons (transport_url, manifest, position, enabled, installed_at)
+                 VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM addons), 1, ?3)
+                 ON CONFLICT (transport_url) DO UPDATE SET manifest = excluded.manifest",
+                params![transport_url, raw, unix_now()],
+            )
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn uninstall_addon(&self, transport_url: &str) -> Result<(), AppError> {
+        self.conn()
+            .execute("DELETE FROM addons WHERE transport_url = ?1", [transport_url])
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    pub fn set_addon_enabled(&self, transport_url: &str, enabled: bool) -> Result<(), AppError> {
+        self.conn()
+            .execute(
+                "UPDATE addons SET enabled = ?2 WHERE transport_url = ?1",
+                params![transport_url, enabled],
+            )
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+
+    /// Positions follow the given order; add-ons not listed keep their relative order after them.
+    pub fn reorder_addons(&self, transport_urls: &[String]) -> Result<(), AppError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE addons SET position = position + ?1", [transport_urls.len()])?;
+        for (position, url) in transport_urls.iter().enumerate() {
+            tx.execute(
+                "UPDATE addons SET position = ?2 WHERE transport_url = ?1",
+                params![url, position],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &Value) -> Result<(), AppError> {
+        let raw = serde_json::to_string(value)?;
+        self.conn()
+            .execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                params![key, raw],
+            )
+            .map(|_| ())
+            .map_err(AppError::from)
+    }
+}
+
+const SESSION_COLUMNS: &str = "video_id, timestamp, watched, duration, season, episode, updated_at";
+
+fn session_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<PlaybackSession> {
+    Ok(PlaybackSession {
+        video_id: row.get(0)?,
+        timestamp: row.get(1)?,
+        watched: row.get(2)?,
+        duration: row.get(3)?,
+        season: row.get(4)?,
+        episode: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+/// Why the database could not be opened; only corruption is worth restoring a backup over.
+enum OpenError {
+    Corrupt(String),
+    Other(AppError),
+}
+
+impl From<rusqlite::Error> for OpenError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => OpenError::Corrupt(e.to_string()),
+            _ => OpenError::Other(e.into()),
+        }
+    }
+}
+
+impl From<AppError> for OpenError {
+    fn from(e: AppError) -> Self {
+        OpenError::Other(e)
+    }
+}
+
+fn open_database(path: &Path) -> Result<Connection, OpenError> {
+    let mut conn = Connection::open(path)?;
+    // journal_mode reports the resulting mode as a row
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    // Make every commit durable, so a crash never loses an acknowledged write
+    conn.pragma_update(None, "synchronous", "FULL")?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(OpenError::Corrupt(format!("integrity check failed: {check}")));
+    }
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+fn open_or_restore(db_path: &Path, backups_dir: &Path) -> Result<Connection, AppError> {
+    match open_database(db_path) {
+        Ok(conn) => Ok(conn),
+        Err(OpenError::Corrupt(reason)) => {
+            eprintln!("Database corrupt ({reason}), restoring from backup");
+            restore_from_backup(backups_dir, db_path)
+        }
+        // Busy, I/O and migration errors would hit a restored backup just the same
+        Err(OpenError::Other(e)) => Err(e),
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), AppError> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)
+            .map_err(|e| AppError::Storage(format!("migration {} failed: {e}", i + 1)))?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// One-time import of playback_sessions.json; the file is renamed afterwards so it is never read again.
+fn import_legacy_sessions(conn: &Connection, path: &Path) -> Result<(), AppError> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let contents = std::fs::read_to_string(path)?;
+    match serde_json::from_str::<LegacyPlaybackSessions>(&contents) {
+        Ok(legacy) => {
+            let now = unix_now();
+            for session in legacy.sessions.values() {
+                let (series_id, episode) = parse_video_id(&session.video_id);
+                // Anything already in the database is newer than the legacy file
+                conn.execute(
+                    "INSERT OR IGNORE INTO sessions (video_id, series_id, season, episode, timestamp, watched, duration, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        session.video_id,
+                        series_id,
+                        episode.map(|(s, _)| s),
+                        episode.map(|(_, e)| e),
+                        session.timestamp,
+                        session.watched,
+                        session.duration,
+                        now
+                    ],
+                )?;
+            }
+        }
+        Err(e) => eprintln!("Skipping unreadable legacy sessions file: {e}"),
+    }
+    std::fs::rename(path, path.with_extension("json.imported")).map_err(AppError::from)
+}
+
+fn backup_path(backups_dir: &Path, taken_at: u64) -> PathBuf {
+    // Zero-padded so lexical order is chronological order
+    backups_dir.join(format!("unstrem-{taken_at:012}.db"))
+}
+
+fn backup_taken_at(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("unstrem-")?
+        .strip_suffix(".db")?
+        .parse()
+        .ok()
+}
+
+// Oldest first
+fn list_backups(backups_dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    if !backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = std::fs::read_dir(backups_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| backup_taken_at(path).is_some())
+        .collect::<Vec<_>>();
+    backups.sort();
+    Ok(backups)
+}
+
+// Snapshot the database into a temp file, fsync it, then rename it into place and prune old copies.
+fn rotate_backups(conn: &Connection, backups_dir: &Path) -> Result<(), AppError> {
+    let now = unix_now();
+    let backups = list_backups(backups_dir)?;
+    let newest = backups.last().and_then(|p| backup_taken_at(p));
+    if newest.is_some_and(|t| now.saturating_sub(t) < BACKUP_INTERVAL_SECS) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(backups_dir)?;
+    let tmp = backups_dir.join("unstrem.db.tmp");
+    // VACUUM INTO refuses to overwrite a leftover from an interrupted backup
+    if tmp.exists() {
+        std::fs::remove_file(&tmp)?;
+    }
+    conn.execute("VACUUM INTO ?1", [tmp.to_string_lossy()])?;
+    std::fs::File::open(&tmp).and_then(|f| f.sync_all())?;
+    std::fs::rename(&tmp, backup_path(backups_dir, now))?;
+
+    let backups = list_backups(backups_dir)?;
+    let excess = backups.len().saturating_sub(BACKUP_COUNT);
+    for old in &backups[..excess] {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(())
+}
+
+// Move the damaged database aside and bring back the newest backup that passes an integrity check.
+fn restore_from_backup(backups_dir: &Path, db_path: &Path) -> Result<Connection, AppError> {
+    if db_path.exists() {
+        // Keep the damaged file around for inspection rather than deleting it
+        let aside = db_path.with_extension(format!("db.corrupt-{}", unix_now()));
+        std::fs::rename(db_path, &aside)?;
+    }
+    for suffix in ["db-wal", "db-shm"] {
+        let _ = std::fs::remove_file(db_path.with_extension(suffix));
+    }
+    for backup in list_backups(backups_dir)?.iter().rev() {
+        std::fs::copy(backup, db_path)?;
+        match open_database(db_path) {
+            Ok(conn) => {
+                eprintln!("Restored database from {}", backup.display());
+                return Ok(conn);
+            }
+            Err(OpenError::Corrupt(reason)) => {
+                eprintln!("Backup {} corrupt: {reason}", backup.display());
+                let _ = std::fs::remove_file(db_path);
+            }
+            Err(OpenError::Other(e)) => {
+                let _ = std::fs::remove_file(db_path);
+                return Err(e);
+            }
+        }
+    }
+    eprintln!("No usable backup found, starting with an empty database");
+    open_database(db_path).map_err(|e| match e {
+        OpenError::Corrupt(reason) => AppError::Storage(reason),
+        OpenError::Other(e) => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> usize {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn migrates_empty_database_to_latest() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+        // Running again is a no-op
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+    }
+
+    // A database as the first release left it
+    fn version_1_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute_batch(
+            "INSERT INTO sessions (imdb_id, timestamp, watched, duration, updated_at)
+                VALUES ('tt0903747:1:2', 2700, 1, 2800, 1700000100),
+                       ('tt0111161', 3600, 0, 8520, 1700000200);
+             INSERT INTO history (imdb_id, watched_at) VALUES ('tt0903747:1:2', 1700000100);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn keys_old_sessions_by_video_id() {
+        let mut conn = version_1_database();
+        migrate(&mut conn).unwrap();
+        let series: Vec<(String, String)> = conn
+            .prepare("SELECT video_id, series_id FROM sessions ORDER BY video_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            series,
+            [
+                ("tt0111161".to_string(), "tt0111161".to_string()),
+                ("tt0903747:1:2".to_string(), "tt0903747".to_string()),
+            ]
+        );
+        let storage = Storage { conn: Arc::new(Mutex::new(conn)) };
+        let episodes = storage.series_sessions("tt0903747").unwrap();
+        assert_eq!(episodes.len(), 1);
+        assert_eq!((episodes[0].season, episodes[0].episode), (Some(1), Some(2)));
+        let movie = storage.get_playback_session("tt0111161").unwrap().unwrap();
+        assert_eq!((movie.season, movie.episode), (None, None));
+        assert_eq!(parse_video_id("tt0903747:1:2"), ("tt0903747", Some((1, 2))));
+        assert_eq!(parse_video_id("tt0111161"), ("tt0111161", None));
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("unstrem-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn imports_legacy_episode_numbers() {
+        let dir = scratch_dir("legacy-import");
+        let path = dir.join("playback_sessions.json");
+        std::fs::write(
+            &path,
+            r#"{"sessions":{"tt0903747:2:5":{"imdb_id":"tt0903747:2:5","timestamp":1260,"watched":false,"duration":2820}}}"#,
+        )
+        .unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        import_legacy_sessions(&conn, &path).unwrap();
+        assert!(dir.join("playback_sessions.json.imported").exists());
+
+        let storage = Storage { conn: Arc::new(Mutex::new(conn)) };
+        let episodes = storage.series_sessions("tt0903747").unwrap();
+        assert_eq!(episodes.len(), 1);
+        assert_eq!((episodes[0].season, episodes[0].episode, episodes[0].timestamp), (Some(2), Some(5), 1260));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn moves_history_into_watch_events() {
+        let mut conn = version_1_database();
+        migrate(&mut conn).unwrap();
+        let event: (String, String, u64, u64) = conn
+            .query_row("SELECT video_id, kind, position, occurred_at FROM watch_events", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        assert_eq!(event, ("tt0903747:1:2".to_string(), "finished".to_string(), 2700, 1700000100));
+        let history: usize = conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'history'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(history, 0);
+    }
+
+    #[test]
+    fn dismissed_session_keeps_progress() {
+        let mut conn = version_1_database();
+        migrate(&mut conn).unwrap();
+        let storage = Storage { conn: Arc::new(Mutex::new(conn)) };
+        let ids = |storage: &Storage| -> Vec<String> {
+            storage.continue_watching(10).unwrap().into_iter().map(|s| s.video_id).collect()
+        };
+        assert_eq!(ids(&storage), ["tt0111161"]);
+
+        storage.dismiss_playback_session("tt0111161").unwrap();
+        assert!(ids(&storage).is_empty());
+        let session = storage.get_playback_session("tt0111161").unwrap().unwrap();
+        assert_eq!(session.timestamp, 3600);
+
+        // Playing it again brings it back
+        storage.save_playback_session(&session).unwrap();
+        assert_eq!(ids(&storage), ["tt0111161"]);
+    }
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    // A database that opens cleanly, tagged so a restore can be told apart
+    fn write_database(path: &Path, marker: &str) {
+        let conn = open_database(path).ok().unwrap();
+        conn.execute("INSERT INTO settings (key, value) VALUES ('marker', ?1)", [marker]).unwrap();
+    }
+
+    fn marker(conn: &Connection) -> String {
+        conn.query_row("SELECT value FROM settings WHERE key = 'marker'", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    const GARBAGE: &[u8] = b"this is not an SQLite database, just a truncated write";
+
+    #[test]
+    fn restores_newest_good_backup() {
+        let dir = scratch_dir("restore-newest");
+        let backups = dir.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        write_database(&backup_path(&backups, 1_700_000_000), "older");
+        write_database(&backup_path(&backups, 1_700_086_400), "newer");
+        let db_path = dir.join("unstrem.db");
+        std::fs::write(&db_path, GARBAGE).unwrap();
+
+        let conn = open_or_restore(&db_path, &backups).unwrap();
+        assert_eq!(marker(&conn), "newer");
+        // The damaged file is kept for inspection
+        assert!(files_in(&dir).iter().any(|f| f.starts_with("unstrem.db.corrupt-")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn skips_corrupt_backup() {
+        let dir = scratch_dir("restore-skip");
+        let backups = dir.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        write_database(&backup_path(&backups, 1_700_000_000), "older");
+        std::fs::write(backup_path(&backups, 1_700_086_400), GARBAGE).unwrap();
+        let db_path = dir.join("unstrem.db");
+        std::fs::write(&db_path, GARBAGE).unwrap();
+
+        let conn = open_or_restore(&db_path, &backups).unwrap();
+        assert_eq!(marker(&conn), "older");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn other_open_errors_do_not_restore() {
+        let dir = scratch_dir("restore-other");
+        let backups = dir.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        write_database(&backup_path(&backups, 1_700_000_000), "backup");
+        // Readable, but migration 1 fails on the table that is already there
+        let db_path = dir.join("unstrem.db");
+        Connection::open(&db_path).unwrap().execute_batch("CREATE TABLE sessions (id INTEGER);").unwrap();
+
+        let error = open_or_restore(&db_path, &backups).err().unwrap();
+        assert!(error.to_string().contains("migration 1 failed"), "{error}");
+        assert!(!files_in(&dir).iter().any(|f| f.contains("corrupt")));
+        let kept: usize = Connection::open(&db_path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'sessions'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kept, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotation_keeps_backup_count() {
+        let dir = scratch_dir("rotate");
+        let backups = dir.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        for day in 0..BACKUP_COUNT as u64 + 2 {
+            write_database(&backup_path(&backups, 1_700_000_000 + day * BACKUP_INTERVAL_SECS), "old");
+        }
+        let db_path = dir.join("unstrem.db");
+        write_database(&db_path, "current");
+        let conn = open_database(&db_path).ok().unwrap();
+
+        rotate_backups(&conn, &backups).unwrap();
+        let kept = list_backups(&backups).unwrap();
+        assert_eq!(kept.len(), BACKUP_COUNT);
+        let newest = kept.last().unwrap();
+        assert!(backup_taken_at(newest).is_some_and(|t| unix_now() - t < 60));
+        assert_eq!(marker(&open_database(newest).ok().unwrap()), "current");
+
+        // A fresh backup is not taken again within the interval
+        rotate_backups(&conn, &backups).unwrap();
+        assert_eq!(list_backups(&backups).unwrap(), kept);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,430 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Reverse;
+
+use crate::addon::SourcedStream;
+
+pub const PREFERENCES_KEY: &str = "stream_preferences";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamBehaviorHints {
+    #[serde(default)]
+    pub not_web_ready: bool,
+    #[serde(default)]
+    pub binge_group: Option<String>,
+    #[serde(default)]
+    pub country_whitelist: Vec<String>,
+    #[serde(default)]
+    pub proxy_headers: Option<Value>,
+    #[serde(default)]
+    pub video_hash: Option<String>,
+    #[serde(default)]
+    pub video_size: Option<u64>,
+    #[serde(default)]
+    pub filename: Option<String>,
+}
+
+/// A stream as returned by the `stream` resource; exactly one of the locators is usually set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stream {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub info_hash: Option<String>,
+    #[serde(default)]
+    pub file_idx: Option<u32>,
+    #[serde(default)]
+    pub yt_id: Option<String>,
+    #[serde(default)]
+    pub external_url: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub behavior_hints: StreamBehaviorHints,
+}
+
+/// What could be read out of the free-form name/title/description of a stream.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamInfo {
+    /// Vertical resolution, e.g. 2160 or 1080.
+    pub resolution: Option<u32>,
+    pub codec: Option<String>,
+    pub hdr: Vec<String>,
+    pub size_bytes: Option<u64>,
+    pub seeders: Option<u32>,
+    /// ISO 639-1 codes.
+    pub languages: Vec<String>,
+    /// Whether a debrid service already has the file; `None` when the add-on does not say.
+    pub cached: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamPreferences {
+    pub preferred_resolution: Option<u32>,
+    pub max_size_gb: Option<f64>,
+    /// ISO 639-1 codes, most preferred first.
+    pub languages: Vec<String>,
+    pub cached_first: bool,
+}
+
+impl Default for StreamPreferences {
+    fn default() -> Self {
+        Self {
+            preferred_resolution: None,
+            max_size_gb: None,
+            languages: Vec::new(),
+            cached_first: true,
+        }
+    }
+}
+
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("spanish", "es"),
+    ("latino", "es"),
+    ("french", "fr"),
+    ("german", "de"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("russian", "ru"),
+    ("japanese", "ja"),
+    ("korean", "ko"),
+    ("chinese", "zh"),
+    ("hindi", "hi"),
+    ("arabic", "ar"),
+    ("dutch", "nl"),
+    ("polish", "pl"),
+    ("turkish", "tr"),
+    ("eng", "en"),
+    ("ita", "it"),
+    ("spa", "es"),
+    ("fre", "fr"),
+    ("ger", "de"),
+];
+
+const LANGUAGE_FLAGS: &[(&str, &str)] = &[
+    ("🇬🇧", "en"),
+    ("🇺🇸", "en"),
+    ("🇪🇸", "es"),
+    ("🇲🇽", "es"),
+    ("🇫🇷", "fr"),
+    ("🇩🇪", "de"),
+    ("🇮🇹", "it"),
+    ("🇵🇹", "pt"),
+    ("🇧🇷", "pt"),
+    ("🇷🇺", "ru"),
+    ("🇯🇵", "ja"),
+    ("🇰🇷", "ko"),
+    ("🇨🇳", "zh"),
+    ("🇮🇳", "hi"),
+    ("🇳🇱", "nl"),
+    ("🇵🇱", "pl"),
+    ("🇹🇷", "tr"),
+];
+
+fn tokens(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace("h.265", "h265")
+        .replace("h.264", "h264")
+        .split(|c: char| !c.is_alphanumeric() && c != '+')
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_resolution(tokens: &[String]) -> Option<u32> {
+    tokens.iter().find_map(|t| match t.as_str() {
+        "2160p" | "4k" | "uhd" => Some(2160),
+        "1440p" | "2k" => Some(1440),
+        "1080p" | "1080i" | "fhd" => Some(1080),
+        "720p" | "hd" => Some(720),
+        "576p" => Some(576),
+        "480p" | "sd" => Some(480),
+        _ => None,
+    })
+}
+
+fn parse_codec(tokens: &[String]) -> Option<String> {
+    tokens.iter().find_map(|t| match t.as_str() {
+        "x265" | "h265" | "hevc" => Some("HEVC".to_string()),
+        "x264" | "h264" | "avc" => Some("AVC".to_string()),
+        "av1" => Some("AV1".to_string()),
+        "vp9" => Some("VP9".to_string()),
+        _ => None,
+    })
+}
+
+fn parse_hdr(text: &str, tokens: &[String]) -> Vec<String> {
+    let mut hdr = Vec::new();
+    let lower = text.to_lowercase();
+    if lower.contains("dolby vision") || tokens.iter().any(|t| matches!(t.as_str(), "dv" | "dovi" | "dolbyvision")) {
+        hdr.push("DV".to_string());
+    }
+    if tokens.iter().any(|t| t == "hdr10+") {
+        hdr.push("HDR10+".to_string());
+    } else if tokens.iter().any(|t| t == "hdr10") {
+        hdr.push("HDR10".to_string());
+    } else if tokens.iter().any(|t| t == "hdr") {
+        hdr.push("HDR".to_string());
+    }
+    hdr
+}
+
+/// Finds the first `<number> <unit>` pair such as `12.4 GB` or `700MiB`.
+fn parse_size(text: &str) -> Option<u64> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() || (i > 0 && chars[i - 1].is_alphanumeric()) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == ',') {
+            i += 1;
+        }
+        let number: String = chars[start..i].iter().map(|c| if *c == ',' { '.' } else { *c }).collect();
+        let mut j = i;
+        while j < chars.len() && chars[j] == ' ' {
+            j += 1;
+        }
+        let unit: String = chars[j..].iter().take_while(|c| c.is_ascii_alphabetic()).collect::<String>().to_lowercase();
+        let multiplier = match unit.as_str() {
+            "tb" | "tib" => 1u64 << 40,
+            "gb" | "gib" => 1u64 << 30,
+            "mb" | "mib" => 1u64 << 20,
+            _ => continue,
+        };
+        if let Ok(value) = number.parse::<f64>() {
+            return Some((value * multiplier as f64) as u64);
+        }
+    }
+    None
+}
+
+/// Seeders are shown as `👤 123` by most torrent add-ons.
+fn parse_seeders(text: &str) -> Option<u32> {
+    let lower = text.to_lowercase();
+    ["👤", "seeders:", "seeders"].iter().find_map(|marker| {
+        let rest = lower.split_once(marker)?.1.trim_start();
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    })
+}
+
+fn parse_languages(text: &str, tokens: &[String]) -> Vec<String> {
+    let mut languages: Vec<String> = Vec::new();
+    let mut push = |code: &str| {
+        if !languages.iter().any(|l| l == code) {
+            languages.push(code.to_string());
+        }
+    };
+    for (flag, code) in LANGUAGE_FLAGS {
+        if text.contains(flag) {
+            push(code);
+        }
+    }
+    for token in tokens {
+        if let Some((_, code)) = LANGUAGE_NAMES.iter().find(|(name, _)| name == token) {
+            push(code);
+        }
+    }
+    languages
+}
+
+// Short names debrid add-ons tag results with: Real-Debrid, AllDebrid, Premiumize, Debrid-Link,
+// TorBox, Offcloud, EasyDebrid and put.io
+const DEBRID_SERVICES: &[&str] = &["rd", "ad", "pm", "dl", "tb", "oc", "ed", "pp"];
+
+// Debrid add-ons mark cached results as `[RD+]`/`⚡` and uncached ones as `[RD download]`/`⏳`
+fn parse_cached(text: &str) -> Option<bool> {
+    let lower = text.to_lowercase();
+    let tagged = |suffix: &str| DEBRID_SERVICES.iter().any(|s| lower.contains(&format!("[{s}{suffix}]")));
+    if tagged("+") || text.contains('⚡') {
+        Some(true)
+    } else if tagged(" download") || text.contains('⏳') {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+pub fn parse_info(stream: &Stream) -> StreamInfo {
+    let text = [
+        stream.name.as_deref(),
+        stream.title.as_deref(),
+        stream.description.as_deref(),
+        stream.behavior_hints.filename.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join("\n");
+    let tokens = tokens(&text);
+    StreamInfo {
+        resolution: parse_resolution(&tokens),
+        codec: parse_codec(&tokens),
+        hdr: parse_hdr(&text, &tokens),
+        size_bytes: stream.behavior_hints.video_size.or_else(|| parse_size(&text)),
+        seeders: parse_seeders(&text),
+        languages: parse_languages(&text, &tokens),
+        cached: parse_cached(&text),
+    }
+}
+
+/// Drop streams over the size cap and sort the rest by preference. The sort is stable,
+/// so add-on order breaks ties.
+pub fn rank(streams: &mut Vec<SourcedStream>, prefs: &StreamPreferences) {
+    if let Some(max_gb) = prefs.max_size_gb {
+        let max_bytes = (max_gb * (1u64 << 30) as f64) as u64;
+        streams.retain(|s| s.info.size_bytes.map_or(true, |size| size <= max_bytes));
+    }
+    streams.sort_by_key(|s| {
+        let info = &s.info;
+        let cached = match (prefs.cached_first, info.cached) {
+            (false, _) => 0,
+            (true, Some(true)) => 0,
+            (true, None) => 1,
+            (true, Some(false)) => 2,
+        };
+        let language = prefs
+            .languages
+            .iter()
+            .position(|pref| info.languages.iter().any(|l| l == pref))
+            .unwrap_or(prefs.languages.len());
+        // Closest to the preferred resolution wins, higher breaks ties; unknown goes last
+        let distance = match (info.resolution, prefs.preferred_resolution) {
+            (None, _) => u32::MAX,
+            (Some(res), Some(pref)) => res.abs_diff(pref),
+            (Some(_), None) => 0,
+        };
+        (cached, language, distance, Reverse(info.resolution), Reverse(info.seeders))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addon::AddonSource;
+
+    const GB: f64 = (1u64 << 30) as f64;
+
+    fn sourced(name: &str, title: &str) -> SourcedStream {
+        let stream = Stream {
+            name: Some(name.to_string()),
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        SourcedStream {
+            source: AddonSource {
+                addon_id: "com.stremio.torrentio.addon".to_string(),
+                addon_name: "Torrentio".to_string(),
+                transport_url: "https://torrentio.strem.fun/manifest.json".to_string(),
+            },
+            info: parse_info(&stream),
+            stream,
+        }
+    }
+
+    fn titles(streams: &[SourcedStream]) -> Vec<&str> {
+        streams.iter().map(|s| s.stream.title.as_deref().unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn size_with_comma_decimals() {
+        let size = parse_size("Dune.Part.Two.2024.1080p.WEB-DL.DDP5.1.H.264\n👤 87 💾 6,35 GB ⚙️ 1337x").unwrap();
+        assert_eq!(size, (6.35 * GB) as u64);
+    }
+
+    #[test]
+    fn codec_is_not_read_as_size() {
+        assert_eq!(parse_size("The.Matrix.1999.2160p.UHD.BluRay.x265-TERMiNAL"), None);
+        let size = parse_size("The.Matrix.1999.2160p.UHD.BluRay.x265-TERMiNAL\n👤 64 💾 22.1 GB ⚙️ TorrentGalaxy");
+        assert_eq!(size, Some((22.1 * GB) as u64));
+    }
+
+    #[test]
+    fn cached_markers() {
+        assert_eq!(parse_cached("[RD+] Torrentio\n4k HDR"), Some(true));
+        assert_eq!(parse_cached("[RD download] Torrentio\n1080p"), Some(false));
+        assert_eq!(parse_cached("Torrentio\n1080p"), None);
+        assert_eq!(parse_cached("[AD+] Torrentio\n1080p"), Some(true));
+    }
+
+    #[test]
+    fn hdr_tags_are_not_cache_markers() {
+        assert_eq!(parse_cached("Movie 2160p [HDR10+]"), None);
+        assert_eq!(parse_cached("Torrentio\n4k [DV+HDR10+]"), None);
+        assert_eq!(parse_cached("[RD download] Torrentio\n4k [HDR10+]"), Some(false));
+    }
+
+    #[test]
+    fn parses_torrentio_stream() {
+        let info = sourced(
+            "[RD+] Torrentio\n4k DV | HDR10",
+            "Oppenheimer.2023.2160p.UHD.BluRay.x265.DV.HDR10.TrueHD.Atmos.7.1\n👤 142 💾 35.84 GB ⚙️ ThePirateBay\n🇬🇧 / 🇮🇹",
+        )
+        .info;
+        assert_eq!(info.resolution, Some(2160));
+        assert_eq!(info.codec.as_deref(), Some("HEVC"));
+        assert_eq!(info.hdr, ["DV", "HDR10"]);
+        assert_eq!(info.seeders, Some(142));
+        assert_eq!(info.languages, ["en", "it"]);
+        assert_eq!(info.cached, Some(true));
+    }
+
+    #[test]
+    fn drops_streams_over_size_cap() {
+        let mut streams = vec![
+            sourced("[RD+] Torrentio\n4k", "Movie.2023.2160p.WEB-DL.x265\n👤 50 💾 15.3 GB ⚙️ 1337x"),
+            sourced("[RD+] Torrentio\n1080p", "Movie.2023.1080p.BluRay.x265\n👤 120 💾 4.4 GB ⚙️ YTS"),
+            sourced("[RD+] Torrentio\n1080p", "Movie.2023.1080p.WEB.x264\n👤 9 ⚙️ EZTV"),
+        ];
+        let prefs = StreamPreferences { max_size_gb: Some(10.0), ..Default::default() };
+        rank(&mut streams, &prefs);
+        // Streams of unknown size are kept
+        assert_eq!(
+            titles(&streams),
+            ["Movie.2023.1080p.BluRay.x265\n👤 120 💾 4.4 GB ⚙️ YTS", "Movie.2023.1080p.WEB.x264\n👤 9 ⚙️ EZTV"]
+        );
+    }
+
+    #[test]
+    fn ranks_cached_then_language_then_resolution() {
+        let mut streams = vec![
+            sourced("[RD download] Torrentio\n1080p", "Movie.2023.1080p.WEB-DL.x264\n👤 300 💾 2.1 GB ⚙️ 1337x"),
+            sourced("[RD+] Torrentio\n4k", "Movie.2023.2160p.WEB-DL.x265\n👤 50 💾 15.3 GB ⚙️ 1337x"),
+            sourced("[RD+] Torrentio\n720p", "Movie.2023.720p.WEB.x264\n👤 20 💾 900 MB ⚙️ EZTV"),
+            sourced("[RD+] Torrentio\n1080p", "Movie.2023.1080p.BluRay.x265\n👤 120 💾 4.4 GB ⚙️ YTS"),
+            sourced("[RD+] Torrentio\n1080p", "Movie.2023.1080p.WEB-DL.x264\n👤 10 💾 3.2 GB ⚙️ ilCorSaRoNeRo\n🇮🇹"),
+        ];
+        let prefs = StreamPreferences {
+            preferred_resolution: Some(1080),
+            languages: vec!["it".to_string()],
+            ..Default::default()
+        };
+        rank(&mut streams, &prefs);
+        let order: Vec<&str> = titles(&streams).iter().map(|t| t.lines().next().unwrap_or_default()).collect();
+        assert_eq!(
+            order,
+            [
+                "Movie.2023.1080p.WEB-DL.x264",
+                "Movie.2023.1080p.BluRay.x265",
+                "Movie.2023.720p.WEB.x264",
+                "Movie.2023.2160p.WEB-DL.x265",
+                "Movie.2023.1080p.WEB-DL.x264",
+            ]
+        );
+        assert_eq!(streams[0].info.languages, ["it"]);
+        assert_eq!(streams[4].info.cached, Some(false));
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+pub const STYLE_KEY: &str = "subtitle_style";
+
+/// How much of a styled (ASS) subtitle's own styling mpv's style options replace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssOverride {
+    /// Keep the subtitle's styling.
+    No,
+    /// Apply font and color options on top of it.
+    Yes,
+    /// Like `yes`, and also override positioning.
+    Force,
+    /// Only apply scale options.
+    #[default]
+    Scale,
+    /// Strip all styling tags.
+    Strip,
+}
+
+impl AssOverride {
+    pub fn as_mpv(self) -> &'static str {
+        match self {
+            AssOverride::No => "no",
+            AssOverride::Yes => "yes",
+            AssOverride::Force => "force",
+            AssOverride::Scale => "scale",
+            AssOverride::Strip => "strip",
+        }
+    }
+}
+
+/// Subtitle appearance and timing; defaults match mpv's. Colors are `#RRGGBB` or `#AARRGGBB`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitleStyle {
+    /// Seconds; positive shows subtitles later.
+    pub delay: f64,
+    pub font_size: u32,
+    pub scale: f64,
+    /// Vertical position as a percentage of the screen height, 100 being the bottom.
+    pub position: u32,
+    pub color: String,
+    /// Draw a box behind the text in this color instead of an outline.
+    pub background: Option<String>,
+    pub border_size: f64,
+    pub border_color: String,
+    pub ass_override: AssOverride,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        Self {
+            delay: 0.0,
+            font_size: 55,
+            scale: 1.0,
+            position: 100,
+            color: "#FFFFFFFF".to_string(),
+            background: None,
+            border_size: 3.0,
+            border_color: "#FF000000".to_string(),
+            ass_override: AssOverride::default(),
+        }
+    }
+}